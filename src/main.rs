@@ -1,214 +1,407 @@
 mod utils;
 
 use crate::utils::{
-    common::{
-        append_or_create_and_write, delete_if_file_exists, wait_for_enter, write_to_txt_file,
-    },
-    data::{determine_ipaddress_type, get_data_from_file},
-    http_request::{acquire_semaphore, is_curl_installed, run_curl},
+    args::Args,
+    common::{delete_if_file_exists, wait_for_enter, write_to_txt_file},
+    config::Config,
+    data::{get_data_from_inputs, report_parse_issues, target_label, target_ports, Target},
+    error::{error_chain, CustomError, EXIT_NO_RESULTS, EXIT_PARTIAL, EXIT_SUCCESS},
+    exclusion::apply_exclusions,
+    filter::filter_and_sort,
+    http_request::{acquire_semaphore, is_curl_installed, run_curl, ProbeResult},
+    jetbrains::{license_server_line, update_license_server_list},
     locations::{check_and_download_location_file, load_location_file},
+    logger::init_logger,
+    order::order_targets,
+    output::{csv_header, csv_record, CsvLayout, NOTE_MARKER},
+    progress::{Progress, Reporter},
+    protocol::is_http3_supported,
+    sampling::{responsive_remainder, sample_targets},
+    speed_test::run_speed_tests,
+    tcp_check::tcp_precheck,
 };
+use clap::Parser;
 use csv::Writer;
 use futures::future::join_all;
-use rand::seq::SliceRandom;
-use std::{fs::File, sync::Arc, time::Instant};
+use log::{error, info, warn};
+use std::{fs::File, path::Path, process::ExitCode, sync::Arc, time::Instant};
 use tokio::sync::{mpsc, Semaphore};
 
+/* 一次运行的统计，用于最后的汇总行和退出码 */
+struct RunSummary {
+    targets: usize,    // 扫描的目标数
+    probes: usize,     // 实际的探测次数（目标 × 端口 × 源地址，TCP预检后只算开放的端口）
+    responsive: usize, // 有响应的探测数
+    written: usize,    // 过滤后写入CSV的结果数
+    failures: usize,   // 失败的步骤数（测速失败、任务异常结束）
+}
+
+/* 一批目标的扫描结果 */
+struct ScanOutcome {
+    results: Vec<ProbeResult>, // 有响应（状态码不为0）的结果
+    probes: usize,             // 探测次数
+    failed: usize,             // 异常结束的任务数
+}
+
+impl RunSummary {
+    fn exit_code(&self) -> u8 {
+        if self.written == 0 {
+            EXIT_NO_RESULTS
+        } else if self.failures > 0 {
+            EXIT_PARTIAL
+        } else {
+            EXIT_SUCCESS
+        }
+    }
+}
+
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() -> ExitCode {
+    let start_time: Instant = Instant::now();
+
+    // 命令行参数、配置文件中的profile合并后的配置；这时日志还没有初始化，错误直接输出到标准错误
+    let config: Arc<Config> = match Config::load(Args::parse()) {
+        Ok(config) => Arc::new(config),
+        Err(err) => {
+            eprintln!("错误：{}", error_chain(&err));
+            return ExitCode::from(err.exit_code());
+        }
+    };
+    if let Err(err) = init_logger(&config) {
+        eprintln!("错误：{}", error_chain(&err));
+        return ExitCode::from(err.exit_code());
+    }
+    info!("使用的配置：{}", config.profile);
+
+    let code: u8 = match run(&config).await {
+        Ok(summary) => {
+            let code = summary.exit_code();
+            if summary.written == 0 {
+                warn!("没有任何结果");
+            }
+            info!(
+                "运行结束：扫描{}个目标，共{}次探测，{}次有响应，写入{}个结果，{}个步骤失败，耗时：{:?}，退出码：{}",
+                summary.targets,
+                summary.probes,
+                summary.responsive,
+                summary.written,
+                summary.failures,
+                start_time.elapsed(),
+                code
+            );
+            code
+        }
+        Err(err) => {
+            let code = err.exit_code();
+            error!("{}", error_chain(&err));
+            info!(
+                "运行结束：耗时：{:?}，退出码：{}",
+                start_time.elapsed(),
+                code
+            );
+            wait_for_enter(&config);
+            code
+        }
+    };
+    ExitCode::from(code)
+}
+
+/* 读取目标、扫描、测速并写入结果；出错时返回给main，由main决定退出码 */
+async fn run(config: &Arc<Config>) -> Result<RunSummary, CustomError> {
     /* 涉及的相关文件 */
-    let data_file: &str = "ips-v4.txt";
-    let output_file: &str = "output.csv";
-    let is_cloudflare_file: &str = "is_cloudflare.txt";
-    let is_jetbrains_license_server_file = "is_jetbrains_license_server.txt";
-    let location_file = "locations.json";
-    let location_url = "https://speed.cloudflare.com/locations";
+    let data_files: &[String] = &config.input;
+    let output_file: &str = &config.output;
+    let is_cloudflare_file: &str = &config.cloudflare_output;
+    let is_jetbrains_license_server_file: &str = &config.jetbrains_output;
+    let location_file: &str = &config.location_file;
+    let location_url: &str = &config.location_url;
 
     // ——————————————————————— 检查curl工具是否安装；检查locations.json文件是否存在，不存在就下载 ———————————————————————
 
     // 检查电脑是否安装有curl，没有安装就退出程序
     if !is_curl_installed().await {
-        println!("本电脑未安装curl命令工具");
-        return Ok(());
+        return Err(CustomError::CommandExecutionFailed(
+            "本电脑未安装curl命令工具".to_string(),
+        ));
+    }
+
+    // HTTP/3需要curl带有HTTP3特性
+    if config.http3 && !is_http3_supported().await {
+        return Err(CustomError::ConfigError(
+            "当前的curl不支持HTTP/3（curl -V 的Features中没有HTTP3），不能使用 --http3".to_string(),
+        ));
     }
 
     // 下载locations.json文件
     check_and_download_location_file(location_file, location_url).await?;
 
-    // ——————————————————————————————— 读取ips-v4.txt文件中的数据，并选择性生成IPv4地址 ————————————————————————————————
+    // ————————————————————————————————— 读取各个输入来源中的数据，并选择性生成IPv4地址 ——————————————————————————————————
 
-    let mut addresses: Vec<String> = get_data_from_file(data_file)?;
+    let (addresses, parse_issues) = get_data_from_inputs(data_files)?;
 
-    // 使用 SliceRandom trait 中的 shuffle 方法打乱向量
-    let mut rng = rand::thread_rng();
-    addresses.shuffle(&mut rng);
+    // 报告解析失败的行
+    report_parse_issues(&parse_issues);
 
-    // 没有数据，就退出程序
-    if addresses.len() == 0 {
-        println!("没有读取到任何数据，请检查{}文件内容", data_file);
-        wait_for_enter();
-        std::process::exit(1);
-    }
+    // 剔除排除列表、保留地址段中的目标，命中禁止扫描列表时拒绝扫描
+    let addresses = apply_exclusions(addresses, config).await?;
 
-    let ports: Vec<u16> = vec![80]; // 这里可以修改为其它端口，或者添加更多的端口
+    // 没有指定种子时随机生成一个，记录下来便于复现扫描顺序和抽样结果
+    let seed: u64 = config.seed.unwrap_or_else(rand::random);
+    if config.uses_seed() {
+        info!(
+            "随机种子：{}（使用 --seed {} 可以复现本次的扫描）",
+            seed, seed
+        );
+    }
 
-    // ————————————————————————————————————————————— 并发执行run_curl函数 —————————————————————————————————————————————
+    // 抽样扫描时，每个/24网段只抽取一部分地址
+    let total_count: usize = addresses.len();
+    let (addresses, rest) = sample_targets(addresses, config, seed);
+    if config.is_sampling() {
+        info!(
+            "抽样扫描：从{}个目标中抽取{}个",
+            total_count,
+            addresses.len()
+        );
+    }
 
-    // 限制并发的数量
-    let concurrent_limit: usize = 100;
+    // 按指定的顺序排列目标
+    let addresses = order_targets(addresses, config.order, seed);
 
-    // 创建通道，receiver用于接收任务结果
-    let (sender, mut receiver) = mpsc::channel(addresses.len() * ports.len());
+    // 没有数据，就退出程序
+    if addresses.is_empty() {
+        return Err(CustomError::NoResults(format!(
+            "没有读取到任何数据，请检查{}的内容",
+            data_files.join(", ")
+        )));
+    }
 
-    let semaphore: Arc<Semaphore> = Arc::new(Semaphore::new(concurrent_limit));
-    let mut tasks = Vec::new();
+    // ————————————————————————————————————————————— 并发执行run_curl函数 —————————————————————————————————————————————
 
-    let data_center_locations: Vec<utils::locations::DataCenterLocations> =
-        load_location_file(location_file)?;
+    let data_center_locations: Arc<Vec<utils::locations::DataCenterLocations>> =
+        Arc::new(load_location_file(location_file)?);
 
-    let start_time: Instant = Instant::now();
+    // TCP预检的结果按扫描批次追加写入，先删除上一次的文件
+    if let Some(tcp_output) = &config.tcp_output {
+        delete_if_file_exists(tcp_output).map_err(|err| CustomError::output(tcp_output, err))?;
+    }
 
-    for address in &addresses {
-        for port in &ports {
-            let semaphore_permit = Arc::clone(&semaphore);
-            let address_clone: String = address.clone();
-            let port_clone: u16 = *port;
-            let sender_clone = sender.clone();
-            let data_center_locations_clone = data_center_locations.clone();
-            let task = tokio::spawn(async move {
-                let permit = acquire_semaphore(&semaphore_permit).await;
-                let result = run_curl(address_clone, port_clone, data_center_locations_clone).await;
-                drop(permit);
-                // 将任务结果发送到通道
-                let send_result = sender_clone.send(result.map_err(|e| e.to_string())).await;
-                // 用于处理"发送失败"
-                if let Err(err) = send_result {
-                    eprintln!("Failed to send result: {:?}", err);
-                }
-            });
-            tasks.push(task);
-        }
+    let mut targets: usize = addresses.len();
+    let outcome = scan(&addresses, &data_center_locations, config).await?;
+    let mut results: Vec<ProbeResult> = outcome.results;
+    let mut probes: usize = outcome.probes;
+    let mut failures: usize = outcome.failed;
+
+    // 对有响应的/24网段，补扫抽样时没有抽中的地址
+    if config.expand_responsive {
+        let remainder = responsive_remainder(rest, &results);
+        info!(
+            "扩展扫描：补扫有响应的/24网段中其余的{}个目标",
+            remainder.len()
+        );
+        let remainder = order_targets(remainder, config.order, seed);
+        let outcome = scan(&remainder, &data_center_locations, config).await?;
+        targets += remainder.len();
+        probes += outcome.probes;
+        results.extend(outcome.results);
+        failures += outcome.failed;
     }
 
-    // 等待所有任务完成
-    join_all(tasks).await;
+    let responsive: usize = results.len();
 
-    // 关闭发送通道
-    drop(sender);
+    // 按条件过滤、排序，最好的结果排在最前面
+    let mut results: Vec<ProbeResult> = filter_and_sort(results, config);
+
+    // ——————————————————————————————————————— 对响应时间最短的若干地址进行下载测速 ———————————————————————————————————————
+
+    failures += run_speed_tests(&mut results, config).await;
 
-    // ———————————————————————————————————— 处理receiver结果，并将结果写入csv文件中 ————————————————————————————————————
+    // ———————————————————————————————————— 处理扫描结果，并将结果写入csv文件中 ————————————————————————————————————
 
     /* 将结果写入文件中 */
-    let mut csv_writer_file: Writer<File> = Writer::from_path(output_file)?;
+    let csv_error = |err: csv::Error| CustomError::output(output_file, err);
+    let io_error = |err: std::io::Error| CustomError::output(output_file, err);
+    let mut csv_writer_file: Writer<File> = Writer::from_path(output_file).map_err(csv_error)?;
 
     // 首先写入CSV的标题
-    csv_writer_file.write_record(&[
-        "网络地址",
-        "响应时间(ms)",
-        "HTTP状态码",
-        "数据中心",
-        "国家代码",
-        "服务器环境",
-    ])?;
-
-    // 存放IP地址、域名、是jetbrains激活服务器的地址
-    let mut ip_addresses_vec: Vec<String> = Vec::new();
-    let mut domain_addresses_vec: Vec<String> = Vec::new();
+    let layout = CsvLayout::new(config, &results);
+    let header = csv_header(&layout);
+    csv_writer_file.write_record(&header).map_err(csv_error)?;
+    csv_writer_file.flush().map_err(io_error)?;
+
+    // 存放Server为cloudflare的地址、是jetbrains激活服务器的地址（与结果的排序一致）
+    let mut cloudflare_addresses_vec: Vec<String> = Vec::new();
     let mut jetbrains_license_server_vec: Vec<String> = Vec::new();
 
     // 用于标记是否在最后写入说明字符串
     let mut flag = false;
 
-    // 接收任务结果并处理
-    while let Ok(result) = receiver.try_recv() {
-        match result {
-            Ok(response) => {
-                let (
-                    address,
-                    port,
-                    response_time,
-                    http_status_code,
-                    cf_ray_code,
-                    country_code,
-                    server_env,
-                    jetbrains_license_server,
-                ) = response;
-                // 剔除不要的数据
-                if http_status_code != 0 {
-                    let ipaddress_type = determine_ipaddress_type(address.as_str());
-                    let is_cloudflare = server_env.to_lowercase().contains("cloudflare");
-                    let is_jetbrains_license =
-                        jetbrains_license_server.to_lowercase().contains("true");
-                    match ipaddress_type {
-                        "Domain Name" => {
-                            if is_cloudflare {
-                                domain_addresses_vec.push(address.clone());
-                            }
-
-                            if is_jetbrains_license {
-                                jetbrains_license_server_vec.push(address.clone());
-                            }
-                            // port = 443;
-                        }
-                        _ => {
-                            if is_cloudflare {
-                                ip_addresses_vec.push(address.clone());
-                            }
-                            if is_jetbrains_license {
-                                let jetbrain_license_address = format!("{}:{}", address, port);
-                                jetbrains_license_server_vec.push(jetbrain_license_address);
-                            }
-                        }
-                    }
-                    flag = true;
-                    csv_writer_file.serialize([
-                        address,
-                        response_time,
-                        http_status_code.to_string(),
-                        cf_ray_code,
-                        country_code,
-                        server_env,
-                    ])?;
-                    csv_writer_file.flush()?;
-                }
-            }
-            Err(_) => {}
+    for response in &results {
+        // 没有自定义模板时：域名使用默认端口时不带端口，其余带端口
+        if response.server.contains(&config.cloudflare_match) {
+            cloudflare_addresses_vec.push(match &config.list_template {
+                Some(template) => template.render(response),
+                None => target_label(&response.address, response.port),
+            });
         }
+        if response.jetbrains_license_server {
+            jetbrains_license_server_vec.push(license_server_line(response, config));
+        }
+        flag = true;
+        csv_writer_file
+            .write_record(csv_record(response, &layout))
+            .map_err(csv_error)?;
+        csv_writer_file.flush().map_err(io_error)?;
     }
 
     // 在后面插入一行，用于说明已经剔除无效数据（可以省略）
     if flag {
-        csv_writer_file.serialize(["", "", "", "", "", "注意：已经剔除无效数据"])?;
-        csv_writer_file.flush()?;
+        let seed_note = format!("随机种子：{}", seed);
+        let mut note = vec![""; header.len()];
+        if config.uses_seed() {
+            note[0] = &seed_note;
+        }
+        note[7] = NOTE_MARKER;
+        csv_writer_file.write_record(&note).map_err(csv_error)?;
+        csv_writer_file.flush().map_err(io_error)?;
     }
 
     // —————————————————————— 分别将cloudflare和jetbrains_license_server相关的地址写入不同的txt文件中 ———————————————————
 
-    // 合并两个向量，域名在前面，IP地址在后面
-    domain_addresses_vec.extend(ip_addresses_vec);
-
     // 转换为字符串
-    let cloudflare_content: String = domain_addresses_vec.join("\n");
-    let license_server_content: String = jetbrains_license_server_vec.join("\n") + "\n"; // 结尾换行
+    let cloudflare_content: String = cloudflare_addresses_vec.join("\n");
 
     // 将Server为cloudflare的地址，写入txt文件中
     if !cloudflare_content.trim().is_empty() {
-        write_to_txt_file(cloudflare_content, is_cloudflare_file);
+        write_to_txt_file(cloudflare_content, is_cloudflare_file)
+            .map_err(|err| CustomError::output(is_cloudflare_file, err))?;
     } else {
-        delete_if_file_exists(is_cloudflare_file)?;
+        delete_if_file_exists(is_cloudflare_file)
+            .map_err(|err| CustomError::output(is_cloudflare_file, err))?;
     }
 
-    // 是Jetbrains的激活服务器的，追加写入txt文件中
-    if !license_server_content.trim().is_empty() {
-        append_or_create_and_write(&license_server_content, is_jetbrains_license_server_file)?;
+    // 是Jetbrains的激活服务器的，与txt文件中已有的地址合并去重（开启验证时剔除失效的旧地址）
+    if !jetbrains_license_server_vec.is_empty()
+        || (config.jetbrains_verify && Path::new(is_jetbrains_license_server_file).exists())
+    {
+        update_license_server_list(
+            is_jetbrains_license_server_file,
+            &jetbrains_license_server_vec,
+            config,
+        )
+        .await?;
     }
 
-    // ———————————————————————————————————————————————————————————————————————————————————————————————————————————————
+    Ok(RunSummary {
+        targets,
+        probes,
+        responsive,
+        written: results.len(),
+        failures,
+    })
+}
 
-    println!("\n注意：如果扫描的目标是域名地址，则不需要添加端口。");
-    println!("所有任务执行完毕，耗时：{:?}", start_time.elapsed());
+/* 并发扫描一批目标，返回有响应的结果、探测次数和异常结束的任务数 */
+async fn scan(
+    addresses: &[Target],
+    data_center_locations: &Arc<Vec<utils::locations::DataCenterLocations>>,
+    config: &Arc<Config>,
+) -> Result<ScanOutcome, CustomError> {
+    // TCP预检后，只剩下端口开放的目标
+    let prechecked: Vec<Target>;
+    let addresses: &[Target] = if config.tcp_precheck {
+        prechecked = tcp_precheck(addresses, config).await?;
+        &prechecked
+    } else {
+        addresses
+    };
+
+    // 限制并发的数量
+    let concurrent_limit: usize = config.concurrency;
+
+    // 指定了多个源地址时，每个目标从每个源地址各请求一次
+    let sources: Vec<Option<String>> = if config.sources.is_empty() {
+        vec![None]
+    } else {
+        config.sources.iter().cloned().map(Some).collect()
+    };
+
+    // 创建通道，receiver用于接收任务结果
+    let task_count: usize = addresses
+        .iter()
+        .map(|target| target_ports(target, config).len())
+        .sum::<usize>()
+        * sources.len();
+    let (sender, mut receiver) = mpsc::channel(task_count.max(1));
 
-    // 关闭接收通道
-    drop(receiver);
-    std::process::exit(0);
+    let semaphore: Arc<Semaphore> = Arc::new(Semaphore::new(concurrent_limit));
+    let mut tasks = Vec::new();
+
+    // 实时显示进度、成功/超时/失败数和目前最快的结果
+    let progress = Progress::new(task_count, config);
+    let reporter = Reporter::start(Arc::clone(&progress), config);
+
+    for source in &sources {
+        for address in addresses {
+            for port in target_ports(address, config) {
+                let semaphore_permit = Arc::clone(&semaphore);
+                let source_clone: Option<String> = source.clone();
+                let address_clone: Target = address.clone();
+                let port_clone: u16 = port;
+                let sender_clone = sender.clone();
+                let data_center_locations_clone = Arc::clone(data_center_locations);
+                let config_clone = Arc::clone(config);
+                let progress_clone = Arc::clone(&progress);
+                let task = tokio::spawn(async move {
+                    let permit = acquire_semaphore(&semaphore_permit).await;
+                    progress_clone.begin();
+                    let result = run_curl(
+                        address_clone,
+                        port_clone,
+                        source_clone,
+                        data_center_locations_clone,
+                        config_clone,
+                    )
+                    .await;
+                    progress_clone.finish(&result);
+                    drop(permit);
+                    // 将任务结果发送到通道
+                    let send_result = sender_clone.send(result.map_err(|e| e.to_string())).await;
+                    // 用于处理"发送失败"
+                    if let Err(err) = send_result {
+                        error!("Failed to send result: {:?}", err);
+                    }
+                });
+                tasks.push(task);
+            }
+        }
+    }
+
+    // 等待所有任务完成，任务panic时记录下来
+    let mut failed: usize = 0;
+    for joined in join_all(tasks).await {
+        if let Err(err) = joined {
+            error!("扫描任务异常结束: {}", err);
+            failed += 1;
+        }
+    }
+    reporter.stop().await;
+
+    // 关闭发送通道
+    drop(sender);
+
+    // 接收任务结果，剔除不要的数据
+    let mut results: Vec<ProbeResult> = Vec::new();
+    while let Ok(result) = receiver.try_recv() {
+        if let Ok(response) = result {
+            if response.status_code != 0 {
+                results.push(response);
+            }
+        }
+    }
+    Ok(ScanOutcome {
+        results,
+        probes: task_count,
+        failed,
+    })
 }
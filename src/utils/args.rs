@@ -0,0 +1,435 @@
+use clap::Parser;
+use serde::{Deserialize, Deserializer};
+use std::path::PathBuf;
+
+use crate::utils::{
+    filter::{SortBy, StatusSet},
+    fingerprint::Fingerprint,
+    logger::{LogFormat, LogLevel},
+    order::Order,
+    ports::{is_tls_port, PortSet},
+    proxy::Proxy,
+    template::LineTemplate,
+};
+
+/* 命令行参数 */
+#[derive(Debug, Clone, Parser)]
+#[command(
+    version,
+    about = "批量扫描IPv4地址、IPv4 CIDR、域名对应站点的 Status Code 和 Server"
+)]
+pub struct Args {
+    /// 配置文件的路径，不指定时如果当前目录存在 scanner.toml 就使用它
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// 使用配置文件中的哪个profile，内置的有 default、cf-edge-hunt、jetbrains-license、status-audit
+    #[arg(long)]
+    pub profile: Option<String>,
+
+    /// 逐行输出每个请求的结果（此时不显示进度条），相当于 --log-level debug；-vv 相当于 --log-level trace
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    #[command(flatten)]
+    pub options: ScanOptions,
+}
+
+/* 扫描选项：既是命令行参数，也是配置文件中一个profile的内容；没有设置的项为None，由下一层补上 */
+#[derive(Debug, Clone, Default, clap::Args, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ScanOptions {
+    /// 扫描目标的来源，可以重复指定：文件、目录、通配符、"-"（标准输入）、xxx.csv[#列名]（之前输出的CSV） [默认: ips-v4.txt]
+    #[arg(long, action = clap::ArgAction::Append)]
+    #[serde(deserialize_with = "string_or_list")]
+    pub input: Option<Vec<String>>,
+
+    /// 扫描结果的CSV文件 [默认: output.csv]
+    #[arg(long)]
+    pub output: Option<String>,
+
+    /// Server为cloudflare的地址列表文件 [默认: is_cloudflare.txt]
+    #[arg(long)]
+    pub cloudflare_output: Option<String>,
+
+    /// JetBrains激活服务器的地址列表文件 [默认: is_jetbrains_license_server.txt]
+    #[arg(long)]
+    pub jetbrains_output: Option<String>,
+
+    /// 数据中心位置文件 [默认: locations.json]
+    #[arg(long)]
+    pub location_file: Option<String>,
+
+    /// 数据中心位置文件不存在时的下载地址 [默认: https://speed.cloudflare.com/locations]
+    #[arg(long)]
+    pub location_url: Option<String>,
+
+    /// 扫描的端口，可以写端口、范围和预设（cf-http、cf-https），例如 80,443,2052-2096,cf-https [默认: 80]
+    #[arg(long)]
+    pub ports: Option<PortSet>,
+
+    /// 请求使用的协议，auto表示按端口选择 [默认: http]
+    #[arg(long, value_enum)]
+    pub scheme: Option<Scheme>,
+
+    /// 扫描IP时作为SNI和Host的域名，URL中使用该域名并通过 curl --connect-to 连接到被扫描的IP，
+    /// 例如Cloudflare边缘IP需要SNI才能完成TLS握手；域名目标不受影响
+    #[arg(long)]
+    pub sni: Option<String>,
+
+    /// 不验证https证书（curl -k），证书与IP或SNI不匹配时也能拿到响应
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub insecure: Option<bool>,
+
+    /// 同时进行的请求数量 [默认: 100]
+    #[arg(long)]
+    pub concurrency: Option<usize>,
+
+    /// 单次请求的超时时间(秒) [默认: 5]
+    #[arg(long)]
+    pub timeout: Option<u64>,
+
+    /// 最大请求次数（含第一次） [默认: 3]
+    #[arg(long)]
+    pub retries: Option<usize>,
+
+    /// 单个目标的总超时时间(秒)，包括重试 [默认: 15]
+    #[arg(long)]
+    pub total_timeout: Option<u64>,
+
+    /// Server中含有该字符串（忽略大小写）就认为是cloudflare [默认: cloudflare]
+    #[arg(long)]
+    pub cloudflare_match: Option<String>,
+
+    /// Location中含有该字符串就认为是JetBrains激活服务器 [默认: account.jetbrains.com/fls-auth]
+    #[arg(long)]
+    pub jetbrains_match: Option<String>,
+
+    /// 每个目标的采样次数，大于1时进入延迟基准测试模式（输出 min/median/p90/max、抖动、丢包率） [默认: 1]
+    #[arg(long)]
+    pub samples: Option<u32>,
+
+    /// 基准测试模式下，两次采样之间的间隔(毫秒) [默认: 200]
+    #[arg(long)]
+    pub sample_interval: Option<u64>,
+
+    /// 扫描结束后，对响应时间最短的前N个地址做下载测速，0表示不测速 [默认: 0]
+    #[arg(long)]
+    pub speed_test_top: Option<usize>,
+
+    /// 测速文件的地址，其中的域名作为Host和SNI，实际连接到被测地址的该URL端口（不是扫描时的端口） [默认: https://speed.cloudflare.com/__down?bytes=200000000]
+    #[arg(long)]
+    pub speed_test_url: Option<String>,
+
+    /// 每个地址的测速时长(秒) [默认: 10]
+    #[arg(long)]
+    pub speed_test_duration: Option<u64>,
+
+    /// 每个地址最多下载的字节数（服务器不支持Range时也在达到后停止），不设置就只受测速时长限制
+    #[arg(long)]
+    pub speed_test_bytes: Option<u64>,
+
+    /// 只保留这些HTTP状态码的结果，例如 200,301-308
+    #[arg(long)]
+    pub include_status: Option<StatusSet>,
+
+    /// 剔除这些HTTP状态码的结果，例如 400-599
+    #[arg(long)]
+    pub exclude_status: Option<StatusSet>,
+
+    /// 只保留服务器环境匹配该正则表达式的结果（忽略大小写）
+    #[arg(long)]
+    pub server_regex: Option<String>,
+
+    /// 只保留这些数据中心的结果，例如 HKG,LAX
+    #[arg(long, value_delimiter = ',')]
+    pub colo: Option<Vec<String>>,
+
+    /// 只保留这些国家代码的结果，例如 HK,US
+    #[arg(long, value_delimiter = ',')]
+    pub country: Option<Vec<String>>,
+
+    /// 只保留响应时间不超过该值(毫秒)的结果
+    #[arg(long)]
+    pub max_latency: Option<f64>,
+
+    /// 结果的排序方式，同时决定txt文件中地址的先后顺序 [默认: latency]
+    #[arg(long, value_enum)]
+    pub sort: Option<SortBy>,
+
+    /// 排序后只保留前N条结果
+    #[arg(long)]
+    pub top: Option<usize>,
+
+    /// txt地址列表每一行的模板，例如 "{ip}:{port}#{colo}-{latency}ms"，
+    /// 可用字段: address ip port path tags target url latency status colo country server speed
+    /// min median p90 max jitter loss dns connect tls ttfb title length sha256 mmh3 fingerprint
+    /// final_url final_status redirects trace_ip trace_loc trace_tls trace_http trace_warp trace_sni
+    /// license_version http_version h2 alt_svc h3 proxy source，
+    /// 以及 header:响应头名称
+    #[arg(long)]
+    pub list_template: Option<LineTemplate>,
+
+    /// 排除列表文件，每行一个IPv4地址、CIDR或域名（"*.example.com"匹配子域名），其中的目标不扫描
+    #[arg(long)]
+    pub exclude_file: Option<String>,
+
+    /// 允许扫描私有、回环、组播等保留地址段，默认跳过；域名目标按本机解析出的IPv4地址判断，
+    /// 使用http、https、socks5h代理时不在本机解析，只按域名本身判断
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub allow_reserved: Option<bool>,
+
+    /// 禁止扫描列表文件，格式同排除列表；有目标命中时拒绝整个扫描 [默认: do-not-scan.txt（存在时）]
+    #[arg(long)]
+    pub do_not_scan_file: Option<String>,
+
+    /// 打乱目标顺序的随机种子，相同的种子得到相同的扫描顺序；不指定时随机生成，并记录在输出中
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// 扫描目标的顺序 [默认: random]
+    #[arg(long, value_enum)]
+    pub order: Option<Order>,
+
+    /// 抽样扫描：每个/24网段随机抽取K个IPv4地址，适合很大的CIDR
+    #[arg(long)]
+    pub sample_per_24: Option<usize>,
+
+    /// 抽样扫描：每个/24网段随机抽取该百分比的IPv4地址（至少1个），例如 5
+    #[arg(long)]
+    pub sample_percent: Option<f64>,
+
+    /// 抽样扫描后，对有响应的/24网段补扫其余地址
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub expand_responsive: Option<bool>,
+
+    /// HTTP请求之前先做TCP连接预检，只请求端口开放的目标
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub tcp_precheck: Option<bool>,
+
+    /// TCP预检的连接超时时间(毫秒) [默认: 1000]
+    #[arg(long)]
+    pub tcp_timeout: Option<u64>,
+
+    /// TCP预检同时进行的连接数量 [默认: 1000]
+    #[arg(long)]
+    pub tcp_concurrency: Option<usize>,
+
+    /// TCP预检结果（open/closed/filtered）的CSV文件，不设置就不保存
+    #[arg(long)]
+    pub tcp_output: Option<String>,
+
+    /// 把这些响应头的值输出为CSV中单独的列（忽略大小写），例如 Via,X-Cache
+    #[arg(long, value_delimiter = ',')]
+    pub header_column: Option<Vec<String>>,
+
+    /// 在CSV中增加“响应头”列，记录全部响应头
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub all_headers: Option<bool>,
+
+    /// 使用GET请求并读取正文，提取标题、计算正文的SHA-256和MMH3哈希（默认只发送HEAD请求）
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub body: Option<bool>,
+
+    /// GET模式下最多读取的正文字节数 [默认: 65536]
+    #[arg(long)]
+    pub body_limit: Option<usize>,
+
+    /// 正文指纹，可以重复指定，格式为 "名称=子串" 或 "子串"，正文中含有该子串就记录其名称；设置后自动使用GET模式
+    #[arg(long, action = clap::ArgAction::Append)]
+    pub body_match: Option<Vec<Fingerprint>>,
+
+    /// 跟随跳转，记录每一跳的地址、状态码和服务器环境，以及最终到达的地址和状态码
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub follow_redirects: Option<bool>,
+
+    /// 跟随跳转时最多跳转的次数 [默认: 5]
+    #[arg(long)]
+    pub max_redirects: Option<u32>,
+
+    /// 请求方法，例如 GET、POST；行内标签 method=GET 可以单独指定 [默认: HEAD，GET模式下为GET]
+    #[arg(long)]
+    pub method: Option<String>,
+
+    /// 行内没有指定路径时请求的路径，例如 /cdn-cgi/trace [默认: /]
+    #[arg(long)]
+    pub path: Option<String>,
+
+    /// 附加的请求头，可以重复指定，例如 "Host: example.com"；行内标签 header="X-A: 1" 追加到后面
+    #[arg(long = "header", action = clap::ArgAction::Append)]
+    #[serde(rename = "header")]
+    pub headers: Option<Vec<String>>,
+
+    /// 请求使用的User-Agent；行内标签 ua="..." 可以单独指定 [默认: curl自己的User-Agent]
+    #[arg(long)]
+    pub user_agent: Option<String>,
+
+    /// 对经过Cloudflare的目标再请求 /cdn-cgi/trace，记录数据中心、出口IP、TLS/HTTP版本、WARP、SNI
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub cf_trace: Option<bool>,
+
+    /// 对疑似的JetBrains激活服务器请求 /rpc/ping.action 验证并记录版本，同时重新验证列表文件中的旧地址，剔除失效的
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub jetbrains_verify: Option<bool>,
+
+    /// 检测目标支持的HTTP协议：实际使用的HTTP版本、https目标能否通过ALPN协商HTTP/2、alt-svc宣告的协议
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub protocols: Option<bool>,
+
+    /// 对https目标再尝试一次只用HTTP/3（QUIC）的请求，需要curl支持HTTP3；开启后也会检测其他协议
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub http3: Option<bool>,
+
+    /// 通过代理发送探测请求，可以重复指定组成代理池，按目标轮流使用：http://host:port（CONNECT隧道）、
+    /// socks5h://host:port（由代理解析域名）、socks5://host:port；不能与 --tcp-precheck、--http3 同时使用
+    #[arg(long = "proxy", action = clap::ArgAction::Append)]
+    #[serde(rename = "proxy")]
+    pub proxies: Option<Vec<Proxy>>,
+
+    /// 没有指定 --proxy 时，使用环境变量（http_proxy、https_proxy、all_proxy）中的代理；默认忽略这些环境变量
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub env_proxy: Option<bool>,
+
+    /// 发出请求的本机IP地址或网卡名称（curl --interface），可以重复指定，同一批目标会从每个源地址各扫描一次；
+    /// 与 --tcp-precheck 同时使用时只能是IP地址
+    #[arg(long = "source", action = clap::ArgAction::Append)]
+    #[serde(rename = "source", deserialize_with = "string_or_list")]
+    pub sources: Option<Vec<String>>,
+
+    /// 扫描时显示进度：终端中是实时刷新的进度条和最快的结果，输出重定向时每5秒打印一行 [默认: true]
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub progress: Option<bool>,
+
+    /// 日志级别：error warn info debug trace，debug会逐行输出每个请求的结果 [默认: info]
+    #[arg(long)]
+    pub log_level: Option<LogLevel>,
+
+    /// 日志格式：text（文字）或 json（每行一个JSON对象） [默认: text]
+    #[arg(long)]
+    pub log_format: Option<LogFormat>,
+
+    /// 同时把日志追加写入这个文件
+    #[arg(long)]
+    pub log_file: Option<String>,
+
+    /// 非交互模式：出错时不等待按Enter键，直接退出；标准输入不是终端（例如cron）时自动开启
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub non_interactive: Option<bool>,
+}
+
+/* 请求使用的协议 */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scheme {
+    Http,
+    Https,
+    /// 按端口选择：443、2053、2083、2087、2096、8443使用https，其余使用http
+    Auto,
+}
+
+impl Scheme {
+    /* 某个端口实际使用的协议 */
+    pub fn for_port(&self, port: u16) -> &'static str {
+        match self {
+            Scheme::Http => "http",
+            Scheme::Https => "https",
+            Scheme::Auto if is_tls_port(port) => "https",
+            Scheme::Auto => "http",
+        }
+    }
+}
+
+/* 配置文件中既可以写一个字符串，也可以写字符串数组 */
+fn string_or_list<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<String>>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(Some(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    }))
+}
+
+impl ScanOptions {
+    /* 用lower补上自己没有设置的项，自己的优先级更高 */
+    pub fn merge(self, lower: ScanOptions) -> ScanOptions {
+        macro_rules! merge_fields {
+            ($($field:ident),* $(,)?) => {
+                ScanOptions { $($field: self.$field.or(lower.$field)),* }
+            };
+        }
+        merge_fields!(
+            input,
+            output,
+            cloudflare_output,
+            jetbrains_output,
+            location_file,
+            location_url,
+            ports,
+            scheme,
+            sni,
+            insecure,
+            concurrency,
+            timeout,
+            retries,
+            total_timeout,
+            cloudflare_match,
+            jetbrains_match,
+            samples,
+            sample_interval,
+            speed_test_top,
+            speed_test_url,
+            speed_test_duration,
+            speed_test_bytes,
+            include_status,
+            exclude_status,
+            server_regex,
+            colo,
+            country,
+            max_latency,
+            sort,
+            top,
+            list_template,
+            exclude_file,
+            allow_reserved,
+            do_not_scan_file,
+            seed,
+            order,
+            sample_per_24,
+            sample_percent,
+            expand_responsive,
+            tcp_precheck,
+            tcp_timeout,
+            tcp_concurrency,
+            tcp_output,
+            header_column,
+            all_headers,
+            body,
+            body_limit,
+            body_match,
+            follow_redirects,
+            max_redirects,
+            method,
+            path,
+            headers,
+            user_agent,
+            cf_trace,
+            jetbrains_verify,
+            protocols,
+            http3,
+            proxies,
+            env_proxy,
+            sources,
+            progress,
+            log_level,
+            log_format,
+            log_file,
+            non_interactive,
+        )
+    }
+}
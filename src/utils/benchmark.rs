@@ -0,0 +1,111 @@
+use crate::utils::http_request::Timings;
+
+/* 多次采样后的延迟统计（单位：毫秒） */
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LatencyStats {
+    pub min: f64,
+    pub median: f64,
+    pub p90: f64,
+    pub max: f64,
+    pub jitter: f64,  // 相邻两次成功采样总耗时之差的平均值
+    pub loss: f64,    // 丢包率(%)，即失败采样占全部采样的比例
+    pub dns: f64,     // DNS解析耗时的中位数
+    pub connect: f64, // TCP连接耗时的中位数
+    pub tls: f64,     // TLS握手耗时的中位数
+    pub ttfb: f64,    // 首字节耗时的中位数（从连接建立完成算起）
+}
+
+impl LatencyStats {
+    /* 根据成功的采样结果和总采样次数计算统计值，没有成功的采样就返回None */
+    pub fn from_samples(samples: &[Timings], attempts: usize) -> Option<Self> {
+        if samples.is_empty() || attempts == 0 {
+            return None;
+        }
+
+        // 抖动按采样的先后顺序计算，必须在排序之前
+        let jitter = if samples.len() > 1 {
+            let diffs: f64 = samples
+                .windows(2)
+                .map(|pair| (pair[1].total - pair[0].total).abs())
+                .sum();
+            diffs / (samples.len() - 1) as f64
+        } else {
+            0.0
+        };
+
+        let totals = sorted(samples.iter().map(|t| t.total));
+
+        Some(LatencyStats {
+            min: totals[0],
+            median: percentile(&totals, 50.0),
+            p90: percentile(&totals, 90.0),
+            max: totals[totals.len() - 1],
+            jitter,
+            loss: (attempts - samples.len()) as f64 * 100.0 / attempts as f64,
+            dns: percentile(&sorted(samples.iter().map(|t| t.dns)), 50.0),
+            connect: percentile(&sorted(samples.iter().map(|t| t.connect)), 50.0),
+            tls: percentile(&sorted(samples.iter().map(|t| t.tls)), 50.0),
+            ttfb: percentile(&sorted(samples.iter().map(|t| t.ttfb)), 50.0),
+        })
+    }
+}
+
+fn sorted(values: impl Iterator<Item = f64>) -> Vec<f64> {
+    let mut values: Vec<f64> = values.collect();
+    values.sort_by(|a, b| a.total_cmp(b));
+    values
+}
+
+/* 线性插值法计算百分位数，values必须是已经排好序的非空数组 */
+fn percentile(values: &[f64], p: f64) -> f64 {
+    let rank = p / 100.0 * (values.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    let weight = rank - lower as f64;
+    values[lower] + (values[upper] - values[lower]) * weight
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(total: f64) -> Timings {
+        Timings {
+            dns: 1.0,
+            connect: total / 4.0,
+            tls: total / 2.0,
+            ttfb: total / 4.0,
+            total,
+        }
+    }
+
+    #[test]
+    fn interpolates_percentiles() {
+        let values = [10.0, 20.0, 30.0, 40.0];
+        assert_eq!(percentile(&values, 0.0), 10.0);
+        assert_eq!(percentile(&values, 50.0), 25.0);
+        assert_eq!(percentile(&values, 100.0), 40.0);
+        assert!((percentile(&values, 90.0) - 37.0).abs() < 1e-9);
+        assert_eq!(percentile(&[5.0], 90.0), 5.0);
+    }
+
+    #[test]
+    fn computes_stats_from_samples() {
+        let samples = [sample(40.0), sample(10.0), sample(30.0), sample(20.0)];
+        let stats = LatencyStats::from_samples(&samples, 5).unwrap();
+        assert_eq!(stats.min, 10.0);
+        assert_eq!(stats.max, 40.0);
+        assert_eq!(stats.median, 25.0);
+        // 抖动按采样顺序：|10-40|、|30-10|、|20-30| 的平均值
+        assert!((stats.jitter - 20.0).abs() < 1e-9);
+        assert_eq!(stats.loss, 20.0);
+        assert_eq!(stats.dns, 1.0);
+        assert_eq!(stats.tls, 12.5);
+    }
+
+    #[test]
+    fn returns_none_without_samples() {
+        assert!(LatencyStats::from_samples(&[], 3).is_none());
+        assert!(LatencyStats::from_samples(&[sample(10.0)], 0).is_none());
+    }
+}
@@ -0,0 +1,103 @@
+use std::{process::Stdio, str};
+use tokio::time::timeout;
+
+use crate::utils::config::Config;
+
+// Cloudflare边缘节点的诊断路径
+pub const CF_TRACE_PATH: &str = "/cdn-cgi/trace";
+
+/* /cdn-cgi/trace 返回的 key=value 信息 */
+#[derive(Debug, Clone, Default)]
+pub struct CfTrace {
+    pub colo: String, // 数据中心的IATA代码，例如 HKG
+    pub loc: String,  // 边缘节点看到的访问者所在国家代码
+    pub ip: String,   // 边缘节点看到的访问者IP，即本机的出口IP
+    pub tls: String,  // TLS版本，http请求为 off
+    pub http: String, // HTTP版本，例如 http/1.1、http/2
+    pub warp: String, // 是否经过WARP，on/off
+    pub sni: String,  // SNI的情况：plaintext、encrypted、off
+}
+
+impl CfTrace {
+    /* 解析 key=value 行，没有colo的不是Cloudflare的trace */
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut trace = CfTrace::default();
+        for (key, value) in text.lines().filter_map(|line| line.split_once('=')) {
+            let value = value.trim().to_string();
+            match key.trim() {
+                "colo" => trace.colo = value.to_uppercase(),
+                "loc" => trace.loc = value.to_uppercase(),
+                "ip" => trace.ip = value,
+                "tls" => trace.tls = value,
+                "http" => trace.http = value,
+                "warp" => trace.warp = value,
+                "sni" => trace.sni = value,
+                _ => {}
+            }
+        }
+        if trace.colo.is_empty() {
+            None
+        } else {
+            Some(trace)
+        }
+    }
+}
+
+/* 请求 "协议://地址:端口/cdn-cgi/trace" 并解析，extra_args是请求头、User-Agent等curl参数 */
+pub async fn fetch_cf_trace(
+    base_url: &str,
+    extra_args: &[String],
+    config: &Config,
+) -> Option<CfTrace> {
+    let output = timeout(
+        config.timeout,
+        tokio::process::Command::new("curl")
+            .arg("-s")
+            .args(extra_args)
+            .arg(format!("{}{}", base_url, CF_TRACE_PATH))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .ok()?
+    .ok()?;
+
+    CfTrace::parse(str::from_utf8(&output.stdout).unwrap_or(""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_trace_fields() {
+        let text = "fl=123f\nh=1.1.1.1\nip=203.0.113.7\nts=1700000000.1\nvisit_scheme=https\n\
+                    uag=curl/8.0\ncolo=hkg\nsliver=none\nhttp=http/2\nloc=hk\ntls=TLSv1.3\n\
+                    sni=plaintext\nwarp=off\ngateway=off\n";
+        let trace = CfTrace::parse(text).unwrap();
+        assert_eq!(trace.colo, "HKG");
+        assert_eq!(trace.loc, "HK");
+        assert_eq!(trace.ip, "203.0.113.7");
+        assert_eq!(trace.http, "http/2");
+        assert_eq!(trace.tls, "TLSv1.3");
+        assert_eq!(trace.sni, "plaintext");
+        assert_eq!(trace.warp, "off");
+    }
+
+    #[test]
+    fn tolerates_crlf_and_spaces() {
+        let trace = CfTrace::parse("colo = LAX \r\nloc=US\r\n").unwrap();
+        assert_eq!(trace.colo, "LAX");
+        assert_eq!(trace.loc, "US");
+        assert!(trace.ip.is_empty());
+    }
+
+    #[test]
+    fn rejects_text_without_colo() {
+        assert!(CfTrace::parse("").is_none());
+        assert!(CfTrace::parse("<html>not found</html>").is_none());
+        assert!(CfTrace::parse("ip=1.2.3.4\nloc=US\n").is_none());
+    }
+}
@@ -1,43 +1,20 @@
 use chrono::Local;
+
+use crate::utils::config::Config;
 use std::{
-    fs::{File, OpenOptions},
+    fs::File,
     io::{self, Write},
     path::Path,
 };
 
+// curl 下载的内容直接丢弃
+pub const NULL_DEVICE: &str = if cfg!(windows) { "NUL" } else { "/dev/null" };
+
 /* 将内容写入txt文件中 */
-pub fn write_to_txt_file(content: String, output_file: &str) {
+pub fn write_to_txt_file(content: String, output_file: &str) -> io::Result<()> {
     let path: &Path = Path::new(output_file);
-    let mut txt_writer_file: File = File::create(&path).expect("Failed to create file");
-    txt_writer_file
-        .write_all(content.as_bytes())
-        .expect("Failed to write all data");
-}
-
-// 追写文件
-pub fn append_or_create_and_write(content: &str, file_path: &str) -> std::io::Result<()> {
-    // 检查文件是否存在
-    let file_exists = Path::new(file_path).exists();
-
-    // 打开文件，设置选项
-    let mut file = match OpenOptions::new()
-        .write(true)
-        .append(file_exists)
-        .create(!file_exists)
-        .open(file_path)
-    {
-        Ok(file) => file,
-        Err(e) => {
-            eprintln!("打开文件失败: {}", e);
-            return Err(e);
-        }
-    };
-
-    if let Err(e) = file.write_all(content.as_bytes()) {
-        eprintln!("写入内容失败: {}", e);
-        return Err(e);
-    }
-    Ok(())
+    let mut txt_writer_file: File = File::create(path)?;
+    txt_writer_file.write_all(content.as_bytes())
 }
 
 /* 如果文件存在就删除文件 */
@@ -58,11 +35,13 @@ pub fn get_current_time() -> String {
     formatted_time
 }
 
-pub fn wait_for_enter() {
+/* 交互模式下等待按Enter键再退出，避免双击运行时窗口一闪而过；非交互模式（cron等）直接返回 */
+pub fn wait_for_enter(config: &Config) {
+    if config.non_interactive {
+        return;
+    }
     print!("按Enter键退出程序>> ");
-    io::stdout().flush().expect("Failed to flush stdout");
+    let _ = io::stdout().flush();
     let mut input = String::new();
-    io::stdin()
-        .read_line(&mut input)
-        .expect("Failed to read line");
+    let _ = io::stdin().read_line(&mut input);
 }
@@ -0,0 +1,524 @@
+use log::LevelFilter;
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    io::{self, IsTerminal},
+    net::IpAddr,
+    path::Path,
+};
+use tokio::time::Duration;
+
+use crate::utils::{
+    args::{Args, ScanOptions, Scheme},
+    data::is_valid_domain,
+    error::CustomError,
+    filter::{SortBy, StatusSet},
+    fingerprint::Fingerprint,
+    logger::{LogFormat, LogLevel},
+    order::Order,
+    ports::PortSet,
+    proxy::Proxy,
+    template::LineTemplate,
+};
+
+// 不指定--config时，默认读取的配置文件
+const DEFAULT_CONFIG_FILE: &str = "scanner.toml";
+
+// 内置的profile，配置文件中同名的profile会覆盖其中的项
+const BUILTIN_PROFILES: &str = r#"
+[profiles.default]
+
+[profiles.cf-edge-hunt]
+ports = "cf-http"
+samples = 5
+server-regex = "cloudflare"
+include-status = "200-499"
+sort = "latency"
+top = 50
+speed-test-top = 10
+list-template = "{ip}:{port}#{colo}-{latency}ms"
+
+[profiles.jetbrains-license]
+ports = [80, 8080]
+include-status = "300-399"
+sort = "latency"
+jetbrains-verify = true
+
+[profiles.status-audit]
+concurrency = 50
+retries = 3
+sort = "status"
+"#;
+
+/* 配置文件的结构 */
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+struct ConfigFile {
+    default_profile: Option<String>,
+    profiles: HashMap<String, ScanOptions>,
+}
+
+/* 最终生效的配置：命令行参数 > 配置文件中的profile > 内置的同名profile > 默认值 */
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub profile: String,
+    pub input: Vec<String>,
+    pub output: String,
+    pub cloudflare_output: String,
+    pub jetbrains_output: String,
+    pub location_file: String,
+    pub location_url: String,
+    pub ports: Vec<u16>,
+    pub scheme: Scheme,
+    pub sni: Option<String>,
+    pub insecure: bool,
+    pub concurrency: usize,
+    pub timeout: Duration,
+    pub retries: usize,
+    pub total_timeout: Duration,
+    pub cloudflare_match: String,
+    pub jetbrains_match: String,
+    pub samples: u32,
+    pub sample_interval: u64,
+    pub speed_test_top: usize,
+    pub speed_test_url: String,
+    pub speed_test_duration: u64,
+    pub speed_test_bytes: Option<u64>,
+    pub include_status: Option<StatusSet>,
+    pub exclude_status: Option<StatusSet>,
+    pub server_regex: Option<Regex>,
+    pub colo: Vec<String>,
+    pub country: Vec<String>,
+    pub max_latency: Option<f64>,
+    pub sort: SortBy,
+    pub top: Option<usize>,
+    pub list_template: Option<LineTemplate>,
+    pub exclude_file: Option<String>,
+    pub allow_reserved: bool,
+    pub do_not_scan_file: Option<String>,
+    pub seed: Option<u64>,
+    pub order: Order,
+    pub sample_per_24: Option<usize>,
+    pub sample_percent: Option<f64>,
+    pub expand_responsive: bool,
+    pub tcp_precheck: bool,
+    pub tcp_timeout: Duration,
+    pub tcp_concurrency: usize,
+    pub tcp_output: Option<String>,
+    pub header_column: Vec<String>,
+    pub all_headers: bool,
+    pub body: bool,
+    pub body_limit: usize,
+    pub body_match: Vec<Fingerprint>,
+    pub follow_redirects: bool,
+    pub max_redirects: u32,
+    pub method: Option<String>,
+    pub path: Option<String>,
+    pub headers: Vec<String>,
+    pub user_agent: Option<String>,
+    pub cf_trace: bool,
+    pub jetbrains_verify: bool,
+    pub protocols: bool,
+    pub http3: bool,
+    pub proxies: Vec<Proxy>,
+    pub env_proxy: bool,
+    pub sources: Vec<String>,
+    pub progress: bool,
+    pub log_level: LevelFilter, // 已经算上了 -v
+    pub log_format: LogFormat,
+    pub log_file: Option<String>,
+    pub non_interactive: bool,
+}
+
+/* 默认profile，即原来写死在程序中的值 */
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            profile: "default".to_string(),
+            input: vec!["ips-v4.txt".to_string()],
+            output: "output.csv".to_string(),
+            cloudflare_output: "is_cloudflare.txt".to_string(),
+            jetbrains_output: "is_jetbrains_license_server.txt".to_string(),
+            location_file: "locations.json".to_string(),
+            location_url: "https://speed.cloudflare.com/locations".to_string(),
+            ports: vec![80],
+            scheme: Scheme::Http,
+            sni: None,
+            insecure: false,
+            concurrency: 100,
+            timeout: Duration::from_secs(5),
+            retries: 3,
+            total_timeout: Duration::from_secs(15),
+            cloudflare_match: "cloudflare".to_string(),
+            jetbrains_match: "account.jetbrains.com/fls-auth".to_string(),
+            samples: 1,
+            sample_interval: 200,
+            speed_test_top: 0,
+            speed_test_url: "https://speed.cloudflare.com/__down?bytes=200000000".to_string(),
+            speed_test_duration: 10,
+            speed_test_bytes: None,
+            include_status: None,
+            exclude_status: None,
+            server_regex: None,
+            colo: Vec::new(),
+            country: Vec::new(),
+            max_latency: None,
+            sort: SortBy::Latency,
+            top: None,
+            list_template: None,
+            exclude_file: None,
+            allow_reserved: false,
+            do_not_scan_file: None,
+            seed: None,
+            order: Order::Random,
+            sample_per_24: None,
+            sample_percent: None,
+            expand_responsive: false,
+            tcp_precheck: false,
+            tcp_timeout: Duration::from_millis(1000),
+            tcp_concurrency: 1000,
+            tcp_output: None,
+            header_column: Vec::new(),
+            all_headers: false,
+            body: false,
+            body_limit: 65536,
+            body_match: Vec::new(),
+            follow_redirects: false,
+            max_redirects: 5,
+            method: None,
+            path: None,
+            headers: Vec::new(),
+            user_agent: None,
+            cf_trace: false,
+            jetbrains_verify: false,
+            protocols: false,
+            http3: false,
+            proxies: Vec::new(),
+            env_proxy: false,
+            sources: Vec::new(),
+            progress: true,
+            log_level: LevelFilter::Info,
+            log_format: LogFormat::Text,
+            log_file: None,
+            non_interactive: false,
+        }
+    }
+}
+
+impl Config {
+    /* 读取配置文件，选出profile，再与命令行参数合并 */
+    pub fn load(args: Args) -> Result<Config, CustomError> {
+        let mut builtin: ConfigFile = toml::from_str(BUILTIN_PROFILES)
+            .map_err(|err| CustomError::config("内置profile有误", err))?;
+
+        let config_file = match &args.config {
+            Some(path) => Some(read_config_file(path)?),
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Some(read_config_file(Path::new(DEFAULT_CONFIG_FILE))?)
+            }
+            None => None,
+        };
+        let mut config_file = config_file.unwrap_or_default();
+
+        let profile_name = args
+            .profile
+            .clone()
+            .or(config_file.default_profile.take())
+            .unwrap_or_else(|| "default".to_string());
+
+        let from_file = config_file.profiles.remove(&profile_name);
+        let from_builtin = builtin.profiles.remove(&profile_name);
+        if from_file.is_none() && from_builtin.is_none() {
+            let mut names: Vec<String> = builtin
+                .profiles
+                .into_keys()
+                .chain(config_file.profiles.into_keys())
+                .collect();
+            names.sort();
+            names.dedup();
+            return Err(CustomError::ConfigError(format!(
+                "找不到名为'{}'的profile，可用的有: {}",
+                profile_name,
+                names.join(", ")
+            )));
+        }
+
+        let options = args
+            .options
+            .merge(from_file.unwrap_or_default())
+            .merge(from_builtin.unwrap_or_default());
+        let mut config = Config::from_options(profile_name, options)?;
+        config.log_level = config.log_level.max(match args.verbose {
+            0 => LevelFilter::Off,
+            1 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        });
+        Ok(config)
+    }
+
+    /* 用默认值补上没有设置的项，并检查各项的取值 */
+    fn from_options(profile: String, options: ScanOptions) -> Result<Config, CustomError> {
+        let default = Config::default();
+
+        let server_regex = match options.server_regex {
+            Some(pattern) => Some(
+                RegexBuilder::new(&pattern)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|err| CustomError::config("server-regex无效", err))?,
+            ),
+            None => None,
+        };
+
+        // 命令行参数和配置文件中的method、path都在这里检查
+        let method = options
+            .method
+            .as_deref()
+            .map(parse_method)
+            .transpose()
+            .map_err(CustomError::ConfigError)?;
+        let path = options
+            .path
+            .as_deref()
+            .map(parse_path)
+            .transpose()
+            .map_err(CustomError::ConfigError)?;
+
+        let config = Config {
+            profile,
+            input: options.input.unwrap_or(default.input),
+            output: options.output.unwrap_or(default.output),
+            cloudflare_output: options
+                .cloudflare_output
+                .unwrap_or(default.cloudflare_output),
+            jetbrains_output: options.jetbrains_output.unwrap_or(default.jetbrains_output),
+            location_file: options.location_file.unwrap_or(default.location_file),
+            location_url: options.location_url.unwrap_or(default.location_url),
+            ports: options
+                .ports
+                .map(PortSet::into_vec)
+                .unwrap_or(default.ports),
+            scheme: options.scheme.unwrap_or(default.scheme),
+            sni: options
+                .sni
+                .map(|name| name.trim().to_lowercase())
+                .or(default.sni),
+            insecure: options.insecure.unwrap_or(default.insecure),
+            concurrency: options.concurrency.unwrap_or(default.concurrency),
+            timeout: options
+                .timeout
+                .map(Duration::from_secs)
+                .unwrap_or(default.timeout),
+            retries: options.retries.unwrap_or(default.retries),
+            total_timeout: options
+                .total_timeout
+                .map(Duration::from_secs)
+                .unwrap_or(default.total_timeout),
+            cloudflare_match: options
+                .cloudflare_match
+                .map(|pattern| pattern.to_lowercase())
+                .unwrap_or(default.cloudflare_match),
+            jetbrains_match: options
+                .jetbrains_match
+                .map(|pattern| pattern.to_lowercase())
+                .unwrap_or(default.jetbrains_match),
+            samples: options.samples.unwrap_or(default.samples),
+            sample_interval: options.sample_interval.unwrap_or(default.sample_interval),
+            speed_test_top: options.speed_test_top.unwrap_or(default.speed_test_top),
+            speed_test_url: options.speed_test_url.unwrap_or(default.speed_test_url),
+            speed_test_duration: options
+                .speed_test_duration
+                .unwrap_or(default.speed_test_duration),
+            speed_test_bytes: options.speed_test_bytes.or(default.speed_test_bytes),
+            include_status: options.include_status.or(default.include_status),
+            exclude_status: options.exclude_status.or(default.exclude_status),
+            server_regex,
+            colo: options.colo.unwrap_or(default.colo),
+            country: options.country.unwrap_or(default.country),
+            max_latency: options.max_latency.or(default.max_latency),
+            sort: options.sort.unwrap_or(default.sort),
+            top: options.top.or(default.top),
+            list_template: options.list_template.or(default.list_template),
+            exclude_file: options.exclude_file.or(default.exclude_file),
+            allow_reserved: options.allow_reserved.unwrap_or(default.allow_reserved),
+            do_not_scan_file: options.do_not_scan_file.or(default.do_not_scan_file),
+            seed: options.seed.or(default.seed),
+            order: options.order.unwrap_or(default.order),
+            sample_per_24: options.sample_per_24.or(default.sample_per_24),
+            sample_percent: options.sample_percent.or(default.sample_percent),
+            expand_responsive: options
+                .expand_responsive
+                .unwrap_or(default.expand_responsive),
+            tcp_precheck: options.tcp_precheck.unwrap_or(default.tcp_precheck),
+            tcp_timeout: options
+                .tcp_timeout
+                .map(Duration::from_millis)
+                .unwrap_or(default.tcp_timeout),
+            tcp_concurrency: options.tcp_concurrency.unwrap_or(default.tcp_concurrency),
+            tcp_output: options.tcp_output.or(default.tcp_output),
+            header_column: options.header_column.unwrap_or(default.header_column),
+            all_headers: options.all_headers.unwrap_or(default.all_headers),
+            body: options.body.unwrap_or(default.body) || options.body_match.is_some(),
+            body_limit: options.body_limit.unwrap_or(default.body_limit),
+            body_match: options.body_match.unwrap_or(default.body_match),
+            follow_redirects: options.follow_redirects.unwrap_or(default.follow_redirects),
+            max_redirects: options.max_redirects.unwrap_or(default.max_redirects),
+            method: method.or(default.method),
+            path: path.or(default.path),
+            headers: options.headers.unwrap_or(default.headers),
+            user_agent: options.user_agent.or(default.user_agent),
+            cf_trace: options.cf_trace.unwrap_or(default.cf_trace),
+            jetbrains_verify: options.jetbrains_verify.unwrap_or(default.jetbrains_verify),
+            protocols: options.protocols.unwrap_or(default.protocols)
+                || options.http3.unwrap_or(default.http3),
+            http3: options.http3.unwrap_or(default.http3),
+            proxies: options.proxies.unwrap_or(default.proxies),
+            env_proxy: options.env_proxy.unwrap_or(default.env_proxy),
+            sources: options.sources.unwrap_or(default.sources),
+            progress: options.progress.unwrap_or(default.progress),
+            log_level: options
+                .log_level
+                .map(LogLevel::to_filter)
+                .unwrap_or(default.log_level),
+            log_format: options.log_format.unwrap_or(default.log_format),
+            log_file: options.log_file.or(default.log_file),
+            non_interactive: options.non_interactive.unwrap_or(default.non_interactive)
+                || !io::stdin().is_terminal(),
+        };
+
+        if config.ports.is_empty() {
+            return Err(CustomError::ConfigError("ports不能为空".to_string()));
+        }
+        if let Some(name) = &config.sni {
+            if !is_valid_domain(name) {
+                return Err(CustomError::ConfigError(format!("sni必须是域名: {}", name)));
+            }
+        }
+        if config.concurrency == 0 {
+            return Err(CustomError::ConfigError("concurrency必须大于0".to_string()));
+        }
+        if config.tcp_concurrency == 0 {
+            return Err(CustomError::ConfigError(
+                "tcp-concurrency必须大于0".to_string(),
+            ));
+        }
+        if config.retries == 0 {
+            return Err(CustomError::ConfigError("retries必须大于0".to_string()));
+        }
+        if config.samples == 0 {
+            return Err(CustomError::ConfigError("samples必须大于0".to_string()));
+        }
+
+        if config.sample_per_24.is_some() && config.sample_percent.is_some() {
+            return Err(CustomError::ConfigError(
+                "sample-per-24和sample-percent只能设置一个".to_string(),
+            ));
+        }
+        if config.sample_per_24 == Some(0) {
+            return Err(CustomError::ConfigError(
+                "sample-per-24必须大于0".to_string(),
+            ));
+        }
+        if let Some(percent) = config.sample_percent {
+            if !(percent > 0.0 && percent <= 100.0) {
+                return Err(CustomError::ConfigError(
+                    "sample-percent必须在0到100之间".to_string(),
+                ));
+            }
+        }
+        if !config.proxies.is_empty() && config.tcp_precheck {
+            return Err(CustomError::ConfigError(
+                "tcp-precheck直接连接目标，不能与proxy同时使用".to_string(),
+            ));
+        }
+        if !config.proxies.is_empty() && config.http3 {
+            return Err(CustomError::ConfigError(
+                "curl不能通过代理发送HTTP/3请求，http3不能与proxy同时使用".to_string(),
+            ));
+        }
+        if config.tcp_precheck
+            && config
+                .sources
+                .iter()
+                .any(|source| source.parse::<IpAddr>().is_err())
+        {
+            return Err(CustomError::ConfigError(
+                "tcp-precheck只能从IP形式的source连接，不支持网卡名称".to_string(),
+            ));
+        }
+        if config.expand_responsive && !config.is_sampling() {
+            return Err(CustomError::ConfigError(
+                "expand-responsive需要同时设置sample-per-24或sample-percent".to_string(),
+            ));
+        }
+
+        Ok(config)
+    }
+
+    /* 是否处于延迟基准测试模式 */
+    pub fn is_benchmark(&self) -> bool {
+        self.samples > 1
+    }
+
+    /* 是否处于抽样扫描模式 */
+    pub fn is_sampling(&self) -> bool {
+        self.sample_per_24.is_some() || self.sample_percent.is_some()
+    }
+
+    /* 扫描顺序或抽样是否用到随机种子，用到时记录下来便于复现 */
+    pub fn uses_seed(&self) -> bool {
+        self.order.uses_seed() || self.is_sampling()
+    }
+}
+
+/* 请求方法只能由字母组成，统一转为大写；行内标签 method=... 也用它检查 */
+pub fn parse_method(value: &str) -> Result<String, String> {
+    let value = value.trim();
+    if value.is_empty() || !value.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(format!("请求方法无效: {}", value));
+    }
+    Ok(value.to_uppercase())
+}
+
+/* 请求路径必须以"/"开头 */
+fn parse_path(value: &str) -> Result<String, String> {
+    if !value.starts_with('/') {
+        return Err(format!("请求路径必须以/开头: {}", value));
+    }
+    Ok(value.to_string())
+}
+
+/* 读取并解析配置文件 */
+fn read_config_file(path: &Path) -> Result<ConfigFile, CustomError> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|err| CustomError::config(format!("读取配置文件'{}'失败", path.display()), err))?;
+    toml::from_str(&content)
+        .map_err(|err| CustomError::config(format!("解析配置文件'{}'失败", path.display()), err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::error::EXIT_CONFIG;
+
+    fn from_toml(text: &str) -> Result<Config, CustomError> {
+        let options: ScanOptions = toml::from_str(text).unwrap();
+        Config::from_options("test".to_string(), options)
+    }
+
+    #[test]
+    fn normalizes_method_and_path_from_file() {
+        let config = from_toml("method = \"get\"\npath = \"/cdn-cgi/trace\"").unwrap();
+        assert_eq!(config.method.as_deref(), Some("GET"));
+        assert_eq!(config.path.as_deref(), Some("/cdn-cgi/trace"));
+    }
+
+    #[test]
+    fn rejects_invalid_method_and_path() {
+        let err = from_toml("path = \"index.html\"").unwrap_err();
+        assert_eq!(err.exit_code(), EXIT_CONFIG);
+        assert!(from_toml("method = \"GE T\"").is_err());
+        assert!(from_toml("method = \"\"").is_err());
+    }
+}
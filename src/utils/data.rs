@@ -1,49 +1,483 @@
-use crate::utils::common::wait_for_enter;
+use crate::utils::{
+    args::Scheme, config::Config, error::CustomError, output::NOTE_MARKER, ports::PortSet,
+};
 use ipnetwork::IpNetwork;
+use log::warn;
 use std::{
+    collections::HashSet,
     fs::File,
     io::{BufRead, BufReader},
-    net::IpAddr,
+    net::{IpAddr, Ipv4Addr},
+    path::Path,
     str::FromStr,
 };
 use url::Url;
 
-/* 读取文件的文件，并解析IP地址 */
-pub fn get_data_from_file(file_path: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-    let file = File::open(file_path);
-    let file_result = file.unwrap_or_else(|err| {
-        eprintln!("打开'{}'文件，报错: {}", file_path, err);
-        wait_for_enter();
-        std::process::exit(1); // 终止程序
-    });
-    let reader = BufReader::new(file_result);
-    let mut unique_addresses = std::collections::HashSet::new();
-
-    for line in reader.lines() {
-        if let Ok(ipaddress_str) = line {
-            let ipaddress_str = ipaddress_str.trim();
-            if !ipaddress_str.is_empty() {
-                let ipaddress_type = determine_ipaddress_type(&ipaddress_str);
-                if ipaddress_type == "IPv4 CIDR" {
-                    let ips_from_cidr = generate_ipv4_ips_from_cidr(&ipaddress_str)?;
-                    unique_addresses.extend(ips_from_cidr);
-                } else if ipaddress_type == "IPv4" {
-                    unique_addresses.insert(ipaddress_str.to_string());
-                } else if ipaddress_type == "Domain Name" {
-                    unique_addresses.insert(ipaddress_str.to_string());
-                } // IPv6 和 IPv6 CIDR 的省略
+// 一个IPv4范围最多展开的地址数量，防止写错范围时耗尽内存
+const MAX_RANGE_SIZE: u64 = 1 << 24;
+
+/* 一个扫描目标，端口、协议、路径没有在行内指定时为None，使用配置中的值 */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub host: String, // IPv4地址或域名
+    pub port: Option<u16>,
+    pub scheme: Option<Scheme>,
+    pub path: Option<String>, // 以"/"开头，可以带查询参数
+    pub tags: Vec<String>,    // 行内标签，例如 "hk"、"method=GET"
+}
+
+impl Target {
+    pub fn new(host: String) -> Self {
+        Target {
+            host,
+            port: None,
+            scheme: None,
+            path: None,
+            tags: Vec::new(),
+        }
+    }
+
+    /* "key=value" 形式的行内标签的值，key忽略大小写，有多个时取第一个 */
+    pub fn tag_value<'a>(&'a self, key: &'a str) -> Option<&'a str> {
+        self.tag_values(key).next()
+    }
+
+    /* "key=value" 形式的行内标签的全部值 */
+    pub fn tag_values<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.tags.iter().filter_map(move |tag| {
+            tag.split_once('=')
+                .filter(|(name, _)| name.eq_ignore_ascii_case(key))
+                .map(|(_, value)| value)
+        })
+    }
+}
+
+/* 目标要扫描的端口：行内指定了端口就只扫描该端口，否则使用配置中的端口 */
+pub fn target_ports(target: &Target, config: &Config) -> Vec<u16> {
+    match target.port {
+        Some(port) => vec![port],
+        None => config.ports.clone(),
+    }
+}
+
+// 判断目标是否重复的依据：主机、端口、协议、路径
+type TargetKey = (String, Option<u16>, Option<Scheme>, Option<String>);
+
+/* 从多个来源收集扫描目标，并去掉重复的 */
+#[derive(Default)]
+struct TargetCollector {
+    targets: Vec<Target>,
+    issues: Vec<CustomError>, // 解析失败的行，都是CustomError::ParseError
+    seen: HashSet<TargetKey>,
+}
+
+impl TargetCollector {
+    /* 逐行解析，line_number从1开始 */
+    fn add_line(&mut self, source: &str, line_number: usize, line: &str) {
+        match parse_line(line) {
+            Ok(parsed) => {
+                for target in parsed {
+                    // 主机、端口、协议、路径都相同的视为重复，保留第一次出现的
+                    let key = (
+                        target.host.clone(),
+                        target.port,
+                        target.scheme,
+                        target.path.clone(),
+                    );
+                    if self.seen.insert(key) {
+                        self.targets.push(target);
+                    }
+                }
+            }
+            Err(reason) => self.issues.push(CustomError::ParseError {
+                path: source.to_string(),
+                line: line_number,
+                reason: format!("\"{}\" {}", line.trim(), reason),
+            }),
+        }
+    }
+
+    fn add_reader(&mut self, source: &str, reader: impl BufRead) -> std::io::Result<()> {
+        for (index, line) in reader.lines().enumerate() {
+            self.add_line(source, index + 1, &line?);
+        }
+        Ok(())
+    }
+
+    /* 读取之前输出的CSV文件中的一列，默认为"网络地址"列；使用默认列时，
+    有"端口"、"协议"、"请求路径"列的（结果CSV、TCP预检的CSV）一并读取，按原来的端口、协议和路径扫描 */
+    fn add_csv(&mut self, source: &str, column: Option<&str>) -> Result<(), CustomError> {
+        let mut reader = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_path(source)
+            .map_err(|err| CustomError::input(source, err))?;
+        let headers = reader
+            .headers()
+            .map_err(|err| CustomError::input(source, err))?
+            .clone();
+        let column_name = column.unwrap_or("网络地址");
+        let position = |name: &str| headers.iter().position(|header| header == name);
+        let (port_index, scheme_index, path_index) = match column {
+            Some(_) => (None, None, None),
+            None => (position("端口"), position("协议"), position("请求路径")),
+        };
+        let index = headers
+            .iter()
+            .position(|header| header == column_name)
+            .ok_or_else(|| CustomError::ParseError {
+                path: source.to_string(),
+                line: 1,
+                reason: format!("没有'{}'列", column_name),
+            })?;
+
+        for (row, record) in reader.records().enumerate() {
+            let record = record.map_err(|err| CustomError::ParseError {
+                path: source.to_string(),
+                line: err
+                    .position()
+                    .map_or(row + 2, |position| position.line() as usize),
+                reason: err.to_string(),
+            })?;
+            // 跳过结果CSV最后的说明行
+            if record.iter().any(|field| field == NOTE_MARKER) {
+                continue;
+            }
+            let value = record.get(index).unwrap_or("").trim();
+            if value.is_empty() {
+                continue;
+            }
+            let field = |index: Option<usize>| {
+                index
+                    .and_then(|index| record.get(index))
+                    .unwrap_or("")
+                    .trim()
+            };
+            let mut target = value.to_string();
+            if !field(port_index).is_empty() {
+                target = format!("{}:{}", target, field(port_index));
+            }
+            // 有协议时写成完整的URL，按原来的协议扫描
+            if !field(scheme_index).is_empty() {
+                target = format!("{}://{}", field(scheme_index), target);
+            }
+            target.push_str(field(path_index));
+            // 第1行是标题，所以数据从第2行开始
+            self.add_line(source, row + 2, &target);
+        }
+        Ok(())
+    }
+}
+
+/*
+ * 读取多个来源的扫描目标，合并并去重，每个来源可以是：
+ *   "-"                 标准输入
+ *   目录                目录下的所有文件
+ *   通配符              例如 lists/ips-*.txt
+ *   xxx.csv[#列名]      之前输出的CSV文件中的一列，默认为"网络地址"列
+ *   其它                普通的文本文件
+ */
+pub fn get_data_from_inputs(
+    inputs: &[String],
+) -> Result<(Vec<Target>, Vec<CustomError>), CustomError> {
+    let mut collector = TargetCollector::default();
+
+    for input in inputs {
+        for source in expand_input(input)? {
+            if source == "-" {
+                collector
+                    .add_reader("-", std::io::stdin().lock())
+                    .map_err(|err| CustomError::input("-", err))?;
+                continue;
+            }
+
+            let (file_path, column) = match source.rsplit_once('#') {
+                Some((path, column)) if path.to_lowercase().ends_with(".csv") => {
+                    (path, Some(column))
+                }
+                _ => (source.as_str(), None),
+            };
+            if file_path.to_lowercase().ends_with(".csv") {
+                collector.add_csv(file_path, column)?;
+                continue;
             }
+
+            let file = File::open(file_path).map_err(|err| CustomError::input(file_path, err))?;
+            collector
+                .add_reader(file_path, BufReader::new(file))
+                .map_err(|err| CustomError::input(file_path, err))?;
         }
     }
 
-    let addresses: Vec<String> = unique_addresses.into_iter().collect();
-    if addresses.is_empty() {
-        eprintln!("文件'{}'不能为空.", file_path);
-        wait_for_enter();
-        std::process::exit(1);
+    if collector.targets.is_empty() {
+        report_parse_issues(&collector.issues);
+        return Err(CustomError::NoResults(format!(
+            "输入'{}'不能为空.",
+            inputs.join(", ")
+        )));
+    }
+
+    Ok((collector.targets, collector.issues))
+}
+
+/* 列出解析失败的行 */
+pub fn report_parse_issues(issues: &[CustomError]) {
+    if issues.is_empty() {
+        return;
+    }
+    warn!("有{}行无法解析，已跳过：", issues.len());
+    for issue in issues {
+        warn!("  {}", issue);
+    }
+}
+
+/* 把目录、通配符展开成具体的文件，其它的原样返回 */
+fn expand_input(input: &str) -> Result<Vec<String>, CustomError> {
+    let path = Path::new(input);
+    if path.is_dir() {
+        let mut files: Vec<String> = std::fs::read_dir(path)
+            .map_err(|err| CustomError::input(input, err))?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect();
+        files.sort();
+        return Ok(files);
+    }
+
+    if input.contains(['*', '?', '[']) {
+        let mut files: Vec<String> = glob::glob(input)
+            .map_err(|err| CustomError::input(input, err))?
+            .filter_map(|entry| entry.ok())
+            .filter(|path| path.is_file())
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect();
+        if files.is_empty() {
+            return Err(CustomError::input(input, "通配符没有匹配到任何文件"));
+        }
+        files.sort();
+        return Ok(files);
+    }
+
+    Ok(vec![input.to_string()])
+}
+
+/*
+ * 解析一行，空行和注释行返回空数组，每行的格式：
+ *   目标 [标签 ...] [# 注释]
+ * 目标可以是：IPv4、IPv4 CIDR、IPv4范围(1.1.1.1-1.1.1.9 或 1.1.1.1-9)、域名，
+ * 都可以带 ":端口" 和 "/路径"；也可以是完整的URL，例如 https://example.com:8443/cdn-cgi/trace
+ * 标签用空白分隔，含空白的值用双引号括起来，例如 ua="Mozilla/5.0 (X11)"
+ */
+fn parse_line(line: &str) -> Result<Vec<Target>, String> {
+    let tokens = tokenize(strip_comment(line))?;
+    let Some((first, tags)) = tokens.split_first() else {
+        return Ok(Vec::new());
+    };
+
+    let mut targets = parse_target(first)?;
+    for target in &mut targets {
+        target.tags = tags.to_vec();
+    }
+    Ok(targets)
+}
+
+/* 去掉 "#" 开头的注释，"#"必须在行首或者前面是空白，URL中的"#"不算注释 */
+fn strip_comment(line: &str) -> &str {
+    let mut previous_is_space = true;
+    for (index, c) in line.char_indices() {
+        if c == '#' && previous_is_space {
+            return &line[..index];
+        }
+        previous_is_space = c.is_whitespace();
     }
+    line
+}
+
+/* 按空白拆分，双引号中的空白不拆分 */
+fn tokenize(text: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
 
-    Ok(addresses)
+    for c in text.chars() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            c if c.is_whitespace() && !in_quotes => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if in_quotes {
+        return Err("双引号没有闭合".to_string());
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    Ok(tokens)
+}
+
+/* 解析目标部分，CIDR和IPv4范围会展开成多个目标 */
+fn parse_target(token: &str) -> Result<Vec<Target>, String> {
+    if token.contains("://") {
+        return parse_url(token).map(|target| vec![target]);
+    }
+
+    // CIDR中也有"/"，所以先整体尝试CIDR（可以带":端口"），再按"/"拆出路径
+    if let Ok((address, ports)) = split_port(token) {
+        match determine_ipaddress_type(address) {
+            "IPv4 CIDR" => {
+                return Ok(combine_ports(
+                    generate_ipv4_ips_from_cidr(address)?,
+                    ports,
+                    None,
+                ));
+            }
+            "IPv6" | "IPv6 CIDR" => return Err("暂不支持IPv6地址".to_string()),
+            _ => {}
+        }
+    }
+
+    let (host_port, path) = match token.find('/') {
+        Some(index) => (&token[..index], Some(token[index..].to_string())),
+        None => (token, None),
+    };
+    let (host, ports) = split_port(host_port)?;
+
+    let hosts: Vec<String> = match host.split_once('-') {
+        Some((start, end)) if start.parse::<Ipv4Addr>().is_ok() => expand_ipv4_range(start, end)?,
+        _ => match determine_ipaddress_type(host) {
+            "IPv4" => vec![host.to_string()],
+            _ if is_valid_domain(host) => vec![host.to_lowercase()],
+            _ => return Err(format!("无法识别的地址: {}", host)),
+        },
+    };
+
+    Ok(combine_ports(hosts, ports, path))
+}
+
+/* 解析完整的URL，只支持http和https */
+pub fn parse_url(token: &str) -> Result<Target, String> {
+    let url = Url::parse(token).map_err(|err| format!("URL无效: {}", err))?;
+    let scheme = match url.scheme() {
+        "http" => Scheme::Http,
+        "https" => Scheme::Https,
+        other => return Err(format!("不支持的协议: {}", other)),
+    };
+    let host = match url.host() {
+        Some(url::Host::Domain(domain)) => domain.to_string(),
+        Some(url::Host::Ipv4(ip)) => ip.to_string(),
+        Some(url::Host::Ipv6(_)) => return Err("暂不支持IPv6地址".to_string()),
+        None => return Err("URL中没有主机".to_string()),
+    };
+
+    let mut path = url.path().to_string();
+    if let Some(query) = url.query() {
+        path = format!("{}?{}", path, query);
+    }
+
+    Ok(Target {
+        port: url.port_or_known_default(),
+        scheme: Some(scheme),
+        path: if path == "/" { None } else { Some(path) },
+        ..Target::new(host)
+    })
+}
+
+/* 拆出末尾的 ":端口"，端口部分可以是端口集合，例如 ":80,443" 或 ":cf-https" */
+fn split_port(text: &str) -> Result<(&str, Option<Vec<u16>>), String> {
+    match text.rsplit_once(':') {
+        // 含多个":"的是IPv6地址，不当作端口处理
+        Some((host, _)) if host.contains(':') => Ok((text, None)),
+        Some((host, ports)) => {
+            let ports: PortSet = ports.parse()?;
+            Ok((host, Some(ports.into_vec())))
+        }
+        None => Ok((text, None)),
+    }
+}
+
+/* 每个主机与每个端口组合成一个目标，没有指定端口时使用配置中的端口 */
+fn combine_ports(hosts: Vec<String>, ports: Option<Vec<u16>>, path: Option<String>) -> Vec<Target> {
+    let ports: Vec<Option<u16>> = match ports {
+        Some(ports) => ports.into_iter().map(Some).collect(),
+        None => vec![None],
+    };
+    let mut targets = Vec::with_capacity(hosts.len() * ports.len());
+    for host in hosts {
+        for port in &ports {
+            targets.push(Target {
+                port: *port,
+                path: path.clone(),
+                ..Target::new(host.clone())
+            });
+        }
+    }
+    targets
+}
+
+/* 地址列表中的写法：域名使用默认端口（80、443）时不带端口，其余为 地址:端口 */
+pub fn target_label(host: &str, port: u16) -> String {
+    match (determine_ipaddress_type(host), port) {
+        ("Domain Name", 80 | 443) => host.to_string(),
+        _ => format!("{}:{}", host, port),
+    }
+}
+
+/* URL中的主机和端口：域名使用该协议的默认端口时不带端口，其余都带上端口 */
+pub fn host_port(host: &str, scheme: &str, port: u16) -> String {
+    match (determine_ipaddress_type(host), scheme, port) {
+        ("Domain Name", "http", 80) | ("Domain Name", "https", 443) => host.to_string(),
+        _ => format!("{}:{}", host, port),
+    }
+}
+
+/* 展开IPv4范围，end可以是完整的地址，也可以只写最后一段 */
+fn expand_ipv4_range(start: &str, end: &str) -> Result<Vec<String>, String> {
+    let start: Ipv4Addr = start
+        .parse()
+        .map_err(|_| format!("范围的起始地址无效: {}", start))?;
+    let end: Ipv4Addr = match end.parse::<Ipv4Addr>() {
+        Ok(end) => end,
+        Err(_) => {
+            let last: u8 = end
+                .parse()
+                .map_err(|_| format!("范围的结束地址无效: {}", end))?;
+            let octets = start.octets();
+            Ipv4Addr::new(octets[0], octets[1], octets[2], last)
+        }
+    };
+
+    let (start, end) = (u32::from(start), u32::from(end));
+    if start > end {
+        return Err("范围的起始地址大于结束地址".to_string());
+    }
+    if u64::from(end - start) + 1 > MAX_RANGE_SIZE {
+        return Err(format!("范围太大，最多{}个地址", MAX_RANGE_SIZE));
+    }
+    Ok((start..=end)
+        .map(|ip| Ipv4Addr::from(ip).to_string())
+        .collect())
+}
+
+/* 域名只能由字母、数字、"-"、"."组成，且至少有一个字母，每段不能以"-"开头或结尾；与URL中的主机一致，localhost等不带"."的主机名也可以 */
+pub fn is_valid_domain(host: &str) -> bool {
+    host.chars().any(|c| c.is_ascii_alphabetic())
+        && !host.starts_with('.')
+        && !host.ends_with('.')
+        && host.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+        })
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.' || c == '_')
+        && determine_ipaddress_type(host) == "Domain Name"
 }
 
 /* 判断address的类型（IPv4/IPv6、IPv4 CIDR、IPv6 CIDR、域名） */
@@ -77,12 +511,193 @@ pub fn determine_ipaddress_type(address: &str) -> &str {
     ""
 }
 
-/* 生成IPv4地址 */
-pub fn generate_ipv4_ips_from_cidr(cidr: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+/* 生成IPv4地址，前缀小于31时跳过网络地址和广播地址 */
+pub fn generate_ipv4_ips_from_cidr(cidr: &str) -> Result<Vec<String>, String> {
     if let Ok(ip_network) = cidr.parse::<IpNetwork>() {
-        let ips: Vec<String> = ip_network.iter().map(|ip| ip.to_string()).collect();
+        if let IpNetwork::V4(network) = ip_network {
+            if 1u64 << (32 - u32::from(network.prefix())) > MAX_RANGE_SIZE {
+                return Err(format!("CIDR太大，最多{}个地址", MAX_RANGE_SIZE));
+            }
+        }
+        let skip_edges = matches!(ip_network, IpNetwork::V4(network) if network.prefix() < 31);
+        let ips: Vec<String> = ip_network
+            .iter()
+            .filter(|ip| {
+                !skip_edges || (*ip != ip_network.network() && *ip != ip_network.broadcast())
+            })
+            .map(|ip| ip.to_string())
+            .collect();
         Ok(ips)
     } else {
         Ok(Vec::new())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hosts(targets: &[Target]) -> Vec<&str> {
+        targets.iter().map(|target| target.host.as_str()).collect()
+    }
+
+    #[test]
+    fn skips_blank_and_comment_lines() {
+        assert!(parse_line("").unwrap().is_empty());
+        assert!(parse_line("   ").unwrap().is_empty());
+        assert!(parse_line("# 注释").unwrap().is_empty());
+        assert!(parse_line("  # 注释").unwrap().is_empty());
+    }
+
+    #[test]
+    fn parses_plain_ip_and_domain() {
+        let targets = parse_line("1.1.1.1").unwrap();
+        assert_eq!(targets, vec![Target::new("1.1.1.1".to_string())]);
+
+        let targets = parse_line("Example.COM").unwrap();
+        assert_eq!(hosts(&targets), ["example.com"]);
+        assert_eq!(targets[0].port, None);
+        assert_eq!(targets[0].path, None);
+    }
+
+    #[test]
+    fn accepts_dotless_hostnames() {
+        assert_eq!(hosts(&parse_line("localhost").unwrap()), ["localhost"]);
+        assert_eq!(
+            hosts(&parse_line("localhost:8080/a").unwrap()),
+            ["localhost"]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_addresses() {
+        assert!(parse_line("1.2.3.999").is_err());
+        assert!(parse_line("-bad-").is_err());
+        assert!(parse_line("exa mple").is_ok()); // 第二个词是标签
+        assert!(parse_line("::1").is_err());
+        assert!(parse_line("2001:db8::/64").is_err());
+    }
+
+    #[test]
+    fn parses_port_sets_and_paths() {
+        let targets = parse_line("1.1.1.1:80,443/cdn-cgi/trace").unwrap();
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[0].port, Some(80));
+        assert_eq!(targets[1].port, Some(443));
+        assert!(targets
+            .iter()
+            .all(|target| target.path.as_deref() == Some("/cdn-cgi/trace")));
+
+        let targets = parse_line("example.com:cf-https").unwrap();
+        assert_eq!(targets.len(), 6);
+
+        assert!(parse_line("1.1.1.1:0").is_err());
+        assert!(parse_line("1.1.1.1:99999").is_err());
+    }
+
+    #[test]
+    fn parses_urls() {
+        let targets = parse_line("https://Example.com:8443/a?b=1#frag").unwrap();
+        assert_eq!(targets.len(), 1);
+        let target = &targets[0];
+        assert_eq!(target.host, "example.com");
+        assert_eq!(target.port, Some(8443));
+        assert_eq!(target.scheme, Some(Scheme::Https));
+        assert_eq!(target.path.as_deref(), Some("/a?b=1"));
+
+        let target = &parse_line("http://1.1.1.1/").unwrap()[0];
+        assert_eq!(target.port, Some(80));
+        assert_eq!(target.path, None);
+
+        assert!(parse_line("ftp://example.com/").is_err());
+        assert!(parse_line("http://[::1]/").is_err());
+    }
+
+    #[test]
+    fn parses_tags_and_comments() {
+        let targets =
+            parse_line(r#"1.1.1.1 hk method=GET ua="Mozilla/5.0 (X11)" # 注释 tag"#).unwrap();
+        assert_eq!(
+            targets[0].tags,
+            ["hk", "method=GET", "ua=Mozilla/5.0 (X11)"]
+        );
+        assert_eq!(targets[0].tag_value("METHOD"), Some("GET"));
+        assert_eq!(targets[0].tag_value("ua"), Some("Mozilla/5.0 (X11)"));
+
+        // URL中的"#"不是注释
+        let targets = parse_line("http://example.com/#a b").unwrap();
+        assert_eq!(targets[0].tags, ["b"]);
+
+        assert!(parse_line(r#"1.1.1.1 ua="unclosed"#).is_err());
+    }
+
+    #[test]
+    fn expands_ranges() {
+        let targets = parse_line("10.0.0.254-10.0.1.1").unwrap();
+        assert_eq!(
+            hosts(&targets),
+            ["10.0.0.254", "10.0.0.255", "10.0.1.0", "10.0.1.1"]
+        );
+
+        let targets = parse_line("1.1.1.1-3:8080").unwrap();
+        assert_eq!(hosts(&targets), ["1.1.1.1", "1.1.1.2", "1.1.1.3"]);
+        assert!(targets.iter().all(|target| target.port == Some(8080)));
+
+        assert!(parse_line("1.1.1.5-1").is_err());
+        assert!(parse_line("1.1.1.1-256").is_err());
+        assert!(parse_line("0.0.0.0-1.0.0.0").is_err());
+    }
+
+    #[test]
+    fn expands_cidr_without_network_and_broadcast() {
+        let targets = parse_line("192.0.2.0/30").unwrap();
+        assert_eq!(hosts(&targets), ["192.0.2.1", "192.0.2.2"]);
+
+        // /31、/32 没有网络地址和广播地址
+        assert_eq!(parse_line("192.0.2.0/31").unwrap().len(), 2);
+        assert_eq!(hosts(&parse_line("192.0.2.7/32").unwrap()), ["192.0.2.7"]);
+
+        let targets = parse_line("192.0.2.0/30:443").unwrap();
+        assert!(targets.iter().all(|target| target.port == Some(443)));
+
+        assert!(generate_ipv4_ips_from_cidr("10.0.0.0/7").is_err());
+    }
+
+    #[test]
+    fn labels_targets() {
+        assert_eq!(target_label("example.com", 443), "example.com");
+        assert_eq!(target_label("example.com", 8443), "example.com:8443");
+        assert_eq!(target_label("1.1.1.1", 80), "1.1.1.1:80");
+    }
+
+    #[test]
+    fn reads_back_result_csv_with_port_scheme_and_path() {
+        let path = std::env::temp_dir().join(format!("data-test-{}.csv", std::process::id()));
+        std::fs::write(
+            &path,
+            format!(
+                "网络地址,端口,协议,响应时间(ms),请求路径\n\
+                 example.com,8444,https,1.00,/a?b=1\n\
+                 1.1.1.1,80,http,2.00,\n\
+                 ,,,,{}\n",
+                NOTE_MARKER
+            ),
+        )
+        .unwrap();
+        let mut collector = TargetCollector::default();
+        let result = collector.add_csv(&path.to_string_lossy(), None);
+        std::fs::remove_file(&path).unwrap();
+        result.unwrap();
+
+        assert!(collector.issues.is_empty());
+        let targets = &collector.targets;
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[0].host, "example.com");
+        assert_eq!(targets[0].port, Some(8444));
+        assert_eq!(targets[0].scheme, Some(Scheme::Https));
+        assert_eq!(targets[0].path.as_deref(), Some("/a?b=1"));
+        assert_eq!(targets[1].host, "1.1.1.1");
+        assert_eq!(targets[1].port, Some(80));
+        assert_eq!(targets[1].scheme, Some(Scheme::Http));
+    }
+}
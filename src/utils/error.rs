@@ -1,15 +1,121 @@
+use std::error::Error as StdError;
 use thiserror::Error;
 
+/* 进程的退出码，便于脚本判断本次运行的结果 */
+pub const EXIT_SUCCESS: u8 = 0; // 成功
+pub const EXIT_FAILURE: u8 = 1; // 其它错误
+pub const EXIT_CONFIG: u8 = 2; // 配置、命令行参数错误（与clap的参数错误一致）
+pub const EXIT_IO: u8 = 3; // 读写文件出错
+pub const EXIT_NO_RESULTS: u8 = 4; // 没有扫描目标，或者没有任何结果
+pub const EXIT_PARTIAL: u8 = 5; // 有结果，但部分步骤失败（测速失败、任务异常等）
+
+// 作为错误来源的底层错误（io、csv、json、网络请求等）
+pub type BoxError = Box<dyn StdError + Send + Sync>;
+
+/* 整个流程中的错误：读取输入、解析、请求目标、位置文件、写入结果和配置；
+底层错误作为source保留，完整的原因用error_chain输出 */
 #[derive(Debug, Error)]
 pub enum CustomError {
     #[error("Command execution failed: {0}")]
     CommandExecutionFailed(String),
+    #[error("请求超时")]
+    Timeout,
+    #[error("配置错误: {0}")]
+    ConfigError(String),
+    #[error("配置错误: {context}")]
+    ConfigSourceError {
+        context: String,
+        #[source]
+        source: BoxError,
+    },
+    #[error("读取输入'{path}'失败")]
+    InputError {
+        path: String,
+        #[source]
+        source: BoxError,
+    },
+    #[error("解析输入'{path}'第{line}行失败: {reason}")]
+    ParseError {
+        path: String,
+        line: usize,
+        reason: String,
+    },
+    #[error("{target} 域名解析失败: {reason}")]
+    DnsError { target: String, reason: String },
+    #[error("{target} 连接失败: {reason}")]
+    ConnectError { target: String, reason: String },
+    #[error("{target} TLS握手失败: {reason}")]
+    TlsError { target: String, reason: String },
+    #[error("{target} 无法解析HTTP响应: {reason}")]
+    HttpParseError { target: String, reason: String },
+    #[error("数据中心位置文件'{path}'处理失败")]
+    LocationFileError {
+        path: String,
+        #[source]
+        source: BoxError,
+    },
+    #[error("写入结果文件'{path}'失败")]
+    OutputError {
+        path: String,
+        #[source]
+        source: BoxError,
+    },
+    #[error("{0}")]
+    NoResults(String),
     #[error("Unexpected error: {0}")]
     UnexpectedError(String),
 }
 
-impl From<std::io::Error> for CustomError {
-    fn from(err: std::io::Error) -> Self {
-        CustomError::CommandExecutionFailed(err.to_string())
+impl CustomError {
+    pub fn config(context: impl Into<String>, source: impl Into<BoxError>) -> Self {
+        CustomError::ConfigSourceError {
+            context: context.into(),
+            source: source.into(),
+        }
+    }
+
+    pub fn input(path: &str, source: impl Into<BoxError>) -> Self {
+        CustomError::InputError {
+            path: path.to_string(),
+            source: source.into(),
+        }
+    }
+
+    pub fn location_file(path: &str, source: impl Into<BoxError>) -> Self {
+        CustomError::LocationFileError {
+            path: path.to_string(),
+            source: source.into(),
+        }
+    }
+
+    pub fn output(path: &str, source: impl Into<BoxError>) -> Self {
+        CustomError::OutputError {
+            path: path.to_string(),
+            source: source.into(),
+        }
+    }
+
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            CustomError::ConfigError(_) | CustomError::ConfigSourceError { .. } => EXIT_CONFIG,
+            CustomError::InputError { .. }
+            | CustomError::ParseError { .. }
+            | CustomError::LocationFileError { .. }
+            | CustomError::OutputError { .. } => EXIT_IO,
+            CustomError::NoResults(_) => EXIT_NO_RESULTS,
+            _ => EXIT_FAILURE,
+        }
+    }
+}
+
+/* 错误及其所有来源，例如 "读取输入'a.txt'失败: No such file or directory (os error 2)" */
+pub fn error_chain(err: &dyn StdError) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
     }
+    message
 }
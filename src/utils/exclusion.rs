@@ -0,0 +1,308 @@
+use futures::stream::{self, StreamExt};
+use ipnetwork::Ipv4Network;
+use log::info;
+use std::{
+    collections::{HashMap, HashSet},
+    net::{IpAddr, Ipv4Addr},
+    path::Path,
+};
+use tokio::{net::lookup_host, time::timeout};
+
+use crate::utils::{
+    config::Config,
+    data::{is_valid_domain, Target},
+    error::CustomError,
+    proxy::Proxy,
+};
+
+// 没有指定do-not-scan-file时，如果当前目录存在该文件就使用它
+const DEFAULT_DO_NOT_SCAN_FILE: &str = "do-not-scan.txt";
+
+// 保留地址段：本网络、私有地址、运营商级NAT、回环、链路本地、文档示例、基准测试、组播、保留及广播
+const RESERVED_RANGES: &[&str] = &[
+    "0.0.0.0/8",
+    "10.0.0.0/8",
+    "100.64.0.0/10",
+    "127.0.0.0/8",
+    "169.254.0.0/16",
+    "172.16.0.0/12",
+    "192.0.0.0/24",
+    "192.0.2.0/24",
+    "192.88.99.0/24",
+    "192.168.0.0/16",
+    "198.18.0.0/15",
+    "198.51.100.0/24",
+    "203.0.113.0/24",
+    "224.0.0.0/4",
+    "240.0.0.0/4",
+];
+
+// 拒绝扫描时，最多列出多少个命中的目标
+const MAX_LISTED_VIOLATIONS: usize = 20;
+
+// 解析目标中的域名时，同时进行的查询数量
+const RESOLVE_CONCURRENCY: usize = 50;
+
+/* 一组IPv4地址段和域名，域名写成 "*.example.com" 或 ".example.com" 时也匹配其子域名 */
+#[derive(Debug, Default)]
+pub struct AddressSet {
+    networks: Vec<Ipv4Network>,
+    domains: Vec<String>,
+}
+
+impl AddressSet {
+    /* 读取文件，每行一个IPv4地址、IPv4 CIDR或域名，"#"后面是注释 */
+    pub fn from_file(file_path: &str) -> Result<Self, CustomError> {
+        let content = std::fs::read_to_string(file_path)
+            .map_err(|err| CustomError::config(format!("读取'{}'失败", file_path), err))?;
+
+        let mut set = AddressSet::default();
+        for (index, line) in content.lines().enumerate() {
+            let entry = line.split('#').next().unwrap_or("").trim();
+            if entry.is_empty() {
+                continue;
+            }
+            if let Ok(network) = entry.parse::<Ipv4Network>() {
+                set.networks.push(network);
+            } else if is_valid_domain(
+                entry
+                    .strip_prefix("*.")
+                    .or_else(|| entry.strip_prefix('.'))
+                    .unwrap_or(entry),
+            ) {
+                set.domains.push(entry.to_lowercase());
+            } else {
+                return Err(CustomError::ConfigError(format!(
+                    "'{}'第{}行无法识别: {}",
+                    file_path,
+                    index + 1,
+                    entry
+                )));
+            }
+        }
+        Ok(set)
+    }
+
+    fn reserved() -> Self {
+        AddressSet {
+            networks: RESERVED_RANGES
+                .iter()
+                .map(|range| range.parse().expect("内置的保留地址段有误"))
+                .collect(),
+            domains: Vec::new(),
+        }
+    }
+
+    pub fn contains(&self, host: &str) -> bool {
+        if let Ok(ip) = host.parse::<Ipv4Addr>() {
+            return self.contains_ip(ip);
+        }
+        let host = host.to_lowercase();
+        self.domains.iter().any(|domain| {
+            match domain
+                .strip_prefix("*.")
+                .or_else(|| domain.strip_prefix('.'))
+            {
+                Some(suffix) => host == suffix || host.ends_with(&format!(".{}", suffix)),
+                None => host == *domain,
+            }
+        })
+    }
+
+    fn contains_ip(&self, ip: Ipv4Addr) -> bool {
+        self.networks.iter().any(|network| network.contains(ip))
+    }
+
+    /* 目标本身匹配，或者目标是域名、解析出的任意一个IPv4地址在地址段中 */
+    fn matches(&self, host: &str, resolved: &HashMap<String, Vec<Ipv4Addr>>) -> bool {
+        self.contains(host)
+            || resolved
+                .get(host)
+                .is_some_and(|ips| ips.iter().any(|ip| self.contains_ip(*ip)))
+    }
+}
+
+/* 解析目标中的域名，返回 域名 -> IPv4地址；解析失败的域名没有地址，只按域名本身匹配。
+使用由代理解析域名的代理（http、https、socks5h）时不在本机解析，以免泄露查询，域名都只按名称匹配 */
+async fn resolve_domains(targets: &[Target], config: &Config) -> HashMap<String, Vec<Ipv4Addr>> {
+    let domains: HashSet<&str> = targets
+        .iter()
+        .map(|target| target.host.as_str())
+        .filter(|host| host.parse::<Ipv4Addr>().is_err())
+        .collect();
+    if !domains.is_empty() && config.proxies.iter().any(Proxy::resolves_remotely) {
+        info!(
+            "使用由代理解析域名的代理，不在本机解析{}个域名，排除列表、保留地址段和禁止扫描列表只按域名本身匹配",
+            domains.len()
+        );
+        return HashMap::new();
+    }
+    stream::iter(domains)
+        .map(|domain| async move {
+            let ips: Vec<Ipv4Addr> = match timeout(config.timeout, lookup_host((domain, 0))).await {
+                Ok(Ok(addresses)) => addresses
+                    .filter_map(|address| match address.ip() {
+                        IpAddr::V4(ip) => Some(ip),
+                        IpAddr::V6(_) => None,
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+            (domain.to_string(), ips)
+        })
+        .buffer_unordered(RESOLVE_CONCURRENCY)
+        .collect()
+        .await
+}
+
+/* 筛选的结果：可以扫描的目标、各个原因剔除的数量，以及命中禁止扫描列表的目标 */
+struct Screening {
+    allowed: Vec<Target>,
+    excluded: usize,
+    reserved: usize,
+    forbidden: Vec<String>,
+}
+
+/* 禁止扫描列表文件：没有指定时，当前目录存在默认的文件就使用它 */
+fn do_not_scan_file(config: &Config) -> Option<&str> {
+    match &config.do_not_scan_file {
+        Some(file_path) => Some(file_path.as_str()),
+        None if Path::new(DEFAULT_DO_NOT_SCAN_FILE).exists() => Some(DEFAULT_DO_NOT_SCAN_FILE),
+        None => None,
+    }
+}
+
+/* 按排除列表、保留地址段和禁止扫描列表筛选目标，域名目标先在本机解析，解析出的IPv4地址也参与地址段的匹配 */
+async fn screen(targets: Vec<Target>, config: &Config) -> Result<Screening, CustomError> {
+    let excluded = match &config.exclude_file {
+        Some(file_path) => AddressSet::from_file(file_path)?,
+        None => AddressSet::default(),
+    };
+    let reserved = if config.allow_reserved {
+        AddressSet::default()
+    } else {
+        AddressSet::reserved()
+    };
+    let do_not_scan = match do_not_scan_file(config) {
+        Some(file_path) => AddressSet::from_file(file_path)?,
+        None => AddressSet::default(),
+    };
+
+    let resolved = resolve_domains(&targets, config).await;
+
+    let mut screening = Screening {
+        allowed: Vec::new(),
+        excluded: 0,
+        reserved: 0,
+        forbidden: Vec::new(),
+    };
+    for target in targets {
+        if excluded.matches(&target.host, &resolved) {
+            screening.excluded += 1;
+        } else if reserved.matches(&target.host, &resolved) {
+            screening.reserved += 1;
+        } else if do_not_scan.matches(&target.host, &resolved) {
+            screening.forbidden.push(target.host);
+        } else {
+            screening.allowed.push(target);
+        }
+    }
+    Ok(screening)
+}
+
+/* 剔除排除列表和保留地址段中的目标；目标命中禁止扫描列表时拒绝整个扫描 */
+pub async fn apply_exclusions(
+    targets: Vec<Target>,
+    config: &Config,
+) -> Result<Vec<Target>, CustomError> {
+    let total = targets.len();
+    let screening = screen(targets, config).await?;
+
+    if screening.excluded > 0 || screening.reserved > 0 {
+        info!(
+            "共{}个目标，按排除列表剔除{}个，按保留地址段剔除{}个（--allow-reserved 可以扫描保留地址段）",
+            total, screening.excluded, screening.reserved
+        );
+    }
+
+    let violations = &screening.forbidden;
+    if !violations.is_empty() {
+        let mut listed: Vec<&str> = violations
+            .iter()
+            .map(String::as_str)
+            .take(MAX_LISTED_VIOLATIONS)
+            .collect();
+        if violations.len() > MAX_LISTED_VIOLATIONS {
+            listed.push("...");
+        }
+        return Err(CustomError::ConfigError(format!(
+            "有{}个目标在禁止扫描列表'{}'中，拒绝扫描: {}",
+            violations.len(),
+            do_not_scan_file(config).unwrap_or_default(),
+            listed.join(", ")
+        )));
+    }
+
+    Ok(screening.allowed)
+}
+
+/* 只保留不在排除列表、保留地址段和禁止扫描列表中的目标，不拒绝扫描；用于重新验证列表文件中的旧地址 */
+pub async fn allowed_targets(
+    targets: Vec<Target>,
+    config: &Config,
+) -> Result<Vec<Target>, CustomError> {
+    Ok(screen(targets, config).await?.allowed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_from(content: &str) -> Result<AddressSet, CustomError> {
+        let path = std::env::temp_dir().join(format!(
+            "exclusion-test-{}-{}.txt",
+            std::process::id(),
+            rand::random::<u32>()
+        ));
+        std::fs::write(&path, content).unwrap();
+        let set = AddressSet::from_file(&path.to_string_lossy());
+        std::fs::remove_file(&path).unwrap();
+        set
+    }
+
+    #[test]
+    fn reads_addresses_and_domains() {
+        let set = set_from("10.0.0.0/8\n1.1.1.1 # 注释\nlocalhost\n*.Example.com\n").unwrap();
+        assert!(set.contains("10.1.2.3"));
+        assert!(set.contains("1.1.1.1"));
+        assert!(!set.contains("1.1.1.2"));
+        assert!(set.contains("localhost"));
+        assert!(set.contains("example.com"));
+        assert!(set.contains("a.b.example.com"));
+        assert!(!set.contains("badexample.com"));
+    }
+
+    #[test]
+    fn rejects_invalid_entries() {
+        assert!(set_from("-bad-\n").is_err());
+        assert!(set_from("example.com:80\n").is_err());
+        assert!(set_from("1.2.3.4/33\n").is_err());
+    }
+
+    #[tokio::test]
+    async fn skips_local_resolution_with_remote_dns_proxy() {
+        let targets = [Target::new("localhost".to_string())];
+        let config = Config {
+            proxies: vec!["socks5h://127.0.0.1:1080".parse().unwrap()],
+            ..Config::default()
+        };
+        assert!(resolve_domains(&targets, &config).await.is_empty());
+
+        let config = Config {
+            proxies: vec!["socks5://127.0.0.1:1080".parse().unwrap()],
+            ..Config::default()
+        };
+        let resolved = resolve_domains(&targets, &config).await;
+        assert!(resolved["localhost"].contains(&Ipv4Addr::LOCALHOST));
+    }
+}
@@ -0,0 +1,159 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Deserializer};
+use std::str::FromStr;
+
+use crate::utils::{config::Config, http_request::ProbeResult};
+
+/* HTTP状态码集合，例如 "200,301-308" */
+#[derive(Debug, Clone)]
+pub struct StatusSet(Vec<(u16, u16)>);
+
+impl StatusSet {
+    pub fn contains(&self, code: u16) -> bool {
+        self.0
+            .iter()
+            .any(|(low, high)| (*low..=*high).contains(&code))
+    }
+}
+
+impl FromStr for StatusSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut ranges = Vec::new();
+        for part in s.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+            let (low, high) = part.split_once('-').unwrap_or((part, part));
+            let low: u16 = low
+                .trim()
+                .parse()
+                .map_err(|_| format!("无效的状态码: {}", part))?;
+            let high: u16 = high
+                .trim()
+                .parse()
+                .map_err(|_| format!("无效的状态码: {}", part))?;
+            if low > high {
+                return Err(format!("状态码范围的起点大于终点: {}", part));
+            }
+            ranges.push((low, high));
+        }
+        if ranges.is_empty() {
+            return Err("状态码集合不能为空".to_string());
+        }
+        Ok(StatusSet(ranges))
+    }
+}
+
+/* 配置文件中与命令行的写法相同，例如 include-status = "200,301-308" */
+impl<'de> Deserialize<'de> for StatusSet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/* 结果的排序方式 */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+    /// 按响应时间从小到大
+    Latency,
+    /// 按HTTP状态码从小到大，状态码相同的按响应时间
+    Status,
+    /// 保持任务完成的先后顺序
+    None,
+}
+
+/* 按配置过滤、排序扫描结果，并只保留前N条 */
+pub fn filter_and_sort(results: Vec<ProbeResult>, config: &Config) -> Vec<ProbeResult> {
+    let mut results: Vec<ProbeResult> = results
+        .into_iter()
+        .filter(|result| is_wanted(result, config))
+        .collect();
+
+    match config.sort {
+        SortBy::Latency => {
+            results.sort_by(|a, b| a.response_time.total_cmp(&b.response_time));
+        }
+        SortBy::Status => {
+            results.sort_by(|a, b| {
+                a.status_code
+                    .cmp(&b.status_code)
+                    .then(a.response_time.total_cmp(&b.response_time))
+            });
+        }
+        SortBy::None => {}
+    }
+
+    if let Some(top) = config.top {
+        results.truncate(top);
+    }
+    results
+}
+
+/* 判断一条结果是否满足全部过滤条件 */
+fn is_wanted(result: &ProbeResult, config: &Config) -> bool {
+    if let Some(include) = &config.include_status {
+        if !include.contains(result.status_code) {
+            return false;
+        }
+    }
+    if let Some(exclude) = &config.exclude_status {
+        if exclude.contains(result.status_code) {
+            return false;
+        }
+    }
+    if let Some(server_regex) = &config.server_regex {
+        if !server_regex.is_match(&result.server) {
+            return false;
+        }
+    }
+    if !config.colo.is_empty()
+        && !config
+            .colo
+            .iter()
+            .any(|c| c.eq_ignore_ascii_case(&result.colo))
+    {
+        return false;
+    }
+    if !config.country.is_empty()
+        && !config
+            .country
+            .iter()
+            .any(|c| c.eq_ignore_ascii_case(&result.country_code))
+    {
+        return false;
+    }
+    if let Some(max_latency) = config.max_latency {
+        if result.response_time > max_latency {
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_codes_and_ranges() {
+        let set: StatusSet = "200, 301-308".parse().unwrap();
+        assert!(set.contains(200));
+        assert!(set.contains(301));
+        assert!(set.contains(304));
+        assert!(set.contains(308));
+        assert!(!set.contains(201));
+        assert!(!set.contains(309));
+        assert!(!set.contains(0));
+    }
+
+    #[test]
+    fn rejects_invalid_sets() {
+        assert!("".parse::<StatusSet>().is_err());
+        assert!(" , ".parse::<StatusSet>().is_err());
+        assert!("abc".parse::<StatusSet>().is_err());
+        assert!("200-".parse::<StatusSet>().is_err());
+        assert!("308-301".parse::<StatusSet>().is_err());
+        assert!("70000".parse::<StatusSet>().is_err());
+    }
+}
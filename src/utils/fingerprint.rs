@@ -0,0 +1,149 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+use sha2::{Digest, Sha256};
+use std::{io::Cursor, str::FromStr, sync::OnceLock};
+
+/* 正文指纹："名称=子串"，只写子串时名称就是子串本身 */
+#[derive(Debug, Clone)]
+pub struct Fingerprint {
+    pub name: String,
+    pub pattern: String,
+}
+
+impl FromStr for Fingerprint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, pattern) = match s.split_once('=') {
+            Some((name, pattern)) if !name.trim().is_empty() => (name.trim(), pattern),
+            _ => (s, s),
+        };
+        if pattern.is_empty() {
+            return Err(format!("指纹的子串不能为空: {}", s));
+        }
+        Ok(Fingerprint {
+            name: name.to_string(),
+            pattern: pattern.to_string(),
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Fingerprint {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/* 响应正文的摘要 */
+#[derive(Debug, Clone, Default)]
+pub struct BodyInfo {
+    pub length: usize,        // 读取到的正文字节数
+    pub truncated: bool,      // 达到字节上限后不再读取
+    pub title: String,        // HTML的<title>
+    pub sha256: String,       // 正文的SHA-256（十六进制）
+    pub mmh3: i32,            // 正文base64编码后的MurmurHash3，与常见的favicon hash算法一致
+    pub matches: Vec<String>, // 命中的指纹名称
+}
+
+impl BodyInfo {
+    pub fn new(body: &[u8], truncated: bool, fingerprints: &[Fingerprint]) -> Self {
+        let text = String::from_utf8_lossy(body);
+        BodyInfo {
+            length: body.len(),
+            truncated,
+            title: extract_title(&text),
+            sha256: hex::encode(Sha256::digest(body)),
+            mmh3: mmh3_base64(body),
+            matches: fingerprints
+                .iter()
+                .filter(|fingerprint| text.contains(&fingerprint.pattern))
+                .map(|fingerprint| fingerprint.name.clone())
+                .collect(),
+        }
+    }
+}
+
+/* 提取HTML的<title>，合并其中的空白并还原常见的字符实体 */
+fn extract_title(text: &str) -> String {
+    static TITLE: OnceLock<Regex> = OnceLock::new();
+    let title = TITLE.get_or_init(|| Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap());
+
+    title
+        .captures(text)
+        .and_then(|captures| captures.get(1))
+        .map(|title| {
+            title
+                .as_str()
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&quot;", "\"")
+                .replace("&#39;", "'")
+                .replace("&amp;", "&")
+        })
+        .unwrap_or_default()
+}
+
+/* 与Shodan的favicon hash相同：base64编码（每76个字符换行，末尾也换行）后计算MurmurHash3，取有符号数 */
+fn mmh3_base64(body: &[u8]) -> i32 {
+    let encoded = STANDARD.encode(body);
+    let mut wrapped = String::with_capacity(encoded.len() + encoded.len() / 76 + 1);
+    for chunk in encoded.as_bytes().chunks(76) {
+        wrapped.push_str(std::str::from_utf8(chunk).unwrap_or_default());
+        wrapped.push('\n');
+    }
+    murmur3::murmur3_32(&mut Cursor::new(wrapped.as_bytes()), 0).unwrap_or_default() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_like_shodan_favicon_hash() {
+        // 期望值由 mmh3.hash(base64.encodebytes(body)) 计算，base64每76个字符换行
+        assert_eq!(mmh3_base64(b"hello"), 1155597304);
+        assert_eq!(mmh3_base64(b""), 0);
+        let body: Vec<u8> = (0..=255u8).cycle().take(768).collect();
+        assert_eq!(mmh3_base64(&body), 1836528006);
+    }
+
+    #[test]
+    fn extracts_title_with_entities_and_whitespace() {
+        assert_eq!(
+            extract_title("<html><head><title>\n  Hello \t  World\n</title></head>"),
+            "Hello World"
+        );
+        assert_eq!(
+            extract_title(
+                r#"<TITLE lang="en">Tom &amp; Jerry &lt;3 &quot;x&quot; &#39;y&#39;</TITLE>"#
+            ),
+            r#"Tom & Jerry <3 "x" 'y'"#
+        );
+        // "&amp;lt;" 还原为 "&lt;"，不会被还原两次
+        assert_eq!(extract_title("<title>a &amp;lt; b</title>"), "a &lt; b");
+        assert_eq!(extract_title("<html><body>no title</body></html>"), "");
+        assert_eq!(extract_title("<title></title>"), "");
+    }
+
+    #[test]
+    fn summarizes_body() {
+        let fingerprints: Vec<Fingerprint> = ["nginx", "WP=wp-content", "missing"]
+            .iter()
+            .map(|text| text.parse().unwrap())
+            .collect();
+        let info = BodyInfo::new(b"<title>x</title>nginx /wp-content/", true, &fingerprints);
+        assert_eq!(info.length, 34);
+        assert!(info.truncated);
+        assert_eq!(info.title, "x");
+        assert_eq!(info.matches, ["nginx", "WP"]);
+        assert_eq!(
+            BodyInfo::new(b"", false, &[]).sha256,
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+}
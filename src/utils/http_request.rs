@@ -1,141 +1,451 @@
-use std::{collections::HashMap, process::Stdio, str, sync::Arc};
+use log::{debug, warn};
+use std::{collections::HashMap, net::Ipv4Addr, process::Stdio, str, sync::Arc};
 use tokio::{
+    io::{AsyncRead, AsyncReadExt},
     sync::{Semaphore, SemaphorePermit},
-    time::{timeout, Duration, Instant},
+    time::{sleep, timeout, Duration, Instant},
 };
+use url::Url;
 
 use crate::utils::{
+    benchmark::LatencyStats,
+    cf_trace::{fetch_cf_trace, CfTrace},
     common::get_current_time,
-    data::determine_ipaddress_type,
+    config::{parse_method, Config},
+    data::{host_port, Target},
     error::CustomError,
+    fingerprint::BodyInfo,
+    jetbrains::{verify_license_server, LicenseServerInfo},
     locations::{find_cca2, DataCenterLocations},
+    protocol::{parse_alt_svc, probe_http3, status_line_version, ProtocolInfo},
+    proxy::{next_proxy, proxy_args, Proxy},
+    redirect::{build_chain, split_header_blocks, RedirectHop},
 };
 
-const MAX_RETRIES: usize = 3; // 最大重试次数（含第一次连接）
-const REQUEST_TIMEOUT: Duration = Duration::from_secs(5); // 设置单个url请求的超时时间
-const TOTAL_TIMEOUT: Duration = Duration::from_secs(15); // 设置单个url请求，总超时时间(包括3次重试)
+// curl -w 输出的标记，用于把响应头和计时信息分开
+const WRITE_OUT_MARKER: &str = "@@curl_write_out@@";
+
+/* 单次请求各阶段的耗时(毫秒)，由curl自己统计，不包含启动curl进程的时间 */
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timings {
+    pub dns: f64,     // DNS解析
+    pub connect: f64, // TCP连接
+    pub tls: f64,     // TLS握手（http请求为0）
+    pub ttfb: f64,    // 连接建立后到收到首字节
+    pub total: f64,   // 总耗时
+}
+
+/* 一个目标的探测结果 */
+#[derive(Debug, Clone)]
+pub struct ProbeResult {
+    pub address: String,
+    pub port: u16,
+    pub scheme: String,    // 实际使用的协议：http或https
+    pub path: String,      // 请求的路径，行内没有指定时为空
+    pub tags: Vec<String>, // 输入文件中的行内标签
+    pub response_time: f64,
+    pub status_code: u16,
+    pub colo: String,
+    pub country_code: String,
+    pub server: String,
+    pub jetbrains_license_server: bool,
+    pub latency_stats: Option<LatencyStats>, // 只有基准测试模式才有
+    pub download_speed: Option<f64>,         // 下载速度(MB/s)，只有参与测速的地址才有
+    pub headers: Vec<(String, String)>,      // 全部响应头，保留原始大小写和重复的响应头
+    pub body: Option<BodyInfo>,              // 只有GET模式才有
+    pub redirects: Vec<RedirectHop>,         // 跳转链，包括最后一跳，只有跟随跳转时才有
+    pub final_url: String,                   // 最终到达的地址，没有跟随跳转时为空
+    pub final_status: u16,                   // 最终的HTTP状态码
+    pub cf_trace: Option<CfTrace>, // /cdn-cgi/trace 的信息，只有开启后经过Cloudflare的目标才有
+    pub license_server: Option<LicenseServerInfo>, // 激活服务器的验证结果，只有开启验证后疑似的目标才有
+    pub protocol: Option<ProtocolInfo>,            // 支持的HTTP协议，只有开启协议检测后才有
+    pub proxy: Option<Proxy>,                      // 探测使用的代理
+    pub source: Option<String>,                    // 发出请求的本机地址或网卡，只有指定了source才有
+}
+
+impl ProbeResult {
+    /* 目标的基础URL：协议://地址:端口，域名使用该协议的默认端口时不带端口 */
+    pub fn base_url(&self) -> String {
+        format!(
+            "{}://{}",
+            self.scheme,
+            host_port(&self.address, &self.scheme, self.port)
+        )
+    }
+
+    /* 获取某个响应头的值（忽略大小写），重复的响应头用", "连接 */
+    pub fn header(&self, name: &str) -> Option<String> {
+        let values: Vec<&str> = self
+            .headers
+            .iter()
+            .filter(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+            .collect();
+        if values.is_empty() {
+            None
+        } else {
+            Some(values.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+impl ProbeResult {
+    /* 测试用：只有地址、端口和状态码200的结果 */
+    pub fn new_for_test(address: &str, port: u16) -> Self {
+        ProbeResult {
+            address: address.to_string(),
+            port,
+            scheme: "http".to_string(),
+            path: String::new(),
+            tags: Vec::new(),
+            response_time: 0.0,
+            status_code: 200,
+            colo: String::new(),
+            country_code: String::new(),
+            server: String::new(),
+            jetbrains_license_server: false,
+            latency_stats: None,
+            download_speed: None,
+            headers: Vec::new(),
+            body: None,
+            redirects: Vec::new(),
+            final_url: String::new(),
+            final_status: 200,
+            cf_trace: None,
+            license_server: None,
+            protocol: None,
+            proxy: None,
+            source: None,
+        }
+    }
+}
+
+/* 单次curl请求解析后的结果 */
+struct CurlResponse {
+    status_code: u16,
+    headers: Vec<String>, // [server, cf-ray, licenseServer]
+    header_lines: Vec<(String, String)>,
+    timings: Timings,
+    body: Option<BodyInfo>,
+    redirects: Vec<RedirectHop>,
+    final_url: String,
+    http_version: String, // 第一个响应的状态行中的HTTP版本
+}
+
+/* 一次探测的请求：方法、请求头、User-Agent、代理，目标的行内标签优先于配置 */
+struct Request {
+    url: String,
+    method: String,
+    headers: Vec<String>,
+    user_agent: Option<String>,
+    proxy: Option<Proxy>,    // 代理池中轮到的代理，同一个目标的所有请求都使用它
+    proxy_args: Vec<String>, // 代理对应的curl参数
+    endpoint_args: Vec<String>, // SNI对应的--connect-to，以及不验证证书的-k
+    source: Option<String>,  // 发出请求的本机地址或网卡
+}
+
+impl Request {
+    fn new(
+        url: String,
+        target: &Target,
+        endpoint_args: Vec<String>,
+        source: Option<String>,
+        config: &Config,
+    ) -> Result<Self, CustomError> {
+        let method = match target.tag_value("method") {
+            Some(method) => Some(parse_method(method).map_err(|reason| {
+                CustomError::ConfigError(format!("{} 的行内标签{}", url, reason))
+            })?),
+            None => config.method.clone(),
+        }
+        .unwrap_or_else(|| if config.body { "GET" } else { "HEAD" }.to_string());
+        let mut headers = config.headers.clone();
+        headers.extend(target.tag_values("header").map(str::to_string));
+        let user_agent = target
+            .tag_value("ua")
+            .map(str::to_string)
+            .or_else(|| config.user_agent.clone());
+        let proxy = next_proxy(config).cloned();
+        let proxy_args = proxy_args(proxy.as_ref(), config);
+
+        Ok(Request {
+            url,
+            method,
+            headers,
+            user_agent,
+            proxy,
+            proxy_args,
+            endpoint_args,
+            source,
+        })
+    }
+
+    /* 请求头、User-Agent、代理和源地址对应的curl参数，trace、ping.action等后续请求也使用 */
+    fn shared_args(&self) -> Vec<String> {
+        let mut args = self.proxy_args.clone();
+        args.extend(self.endpoint_args.iter().cloned());
+        if let Some(source) = &self.source {
+            args.push("--interface".to_string());
+            args.push(source.clone());
+        }
+        for header in &self.headers {
+            args.push("-H".to_string());
+            args.push(header.clone());
+        }
+        if let Some(user_agent) = &self.user_agent {
+            args.push("-A".to_string());
+            args.push(user_agent.clone());
+        }
+        args
+    }
+
+    /* 请求头、User-Agent和方法对应的curl参数，HEAD和GET由调用方决定使用-I还是读取正文 */
+    fn curl_args(&self) -> Vec<String> {
+        let mut args = self.shared_args();
+        if self.method != "HEAD" && self.method != "GET" {
+            args.push("-X".to_string());
+            args.push(self.method.clone());
+        }
+        args
+    }
+}
+
+/* URL中的主机和对应的curl参数：扫描IP时，指定了sni就用该域名作为URL中的主机（即SNI和Host），
+通过--connect-to连接到该IP；指定了insecure时不验证证书 */
+pub fn endpoint(host: &str, port: u16, config: &Config) -> (String, Vec<String>) {
+    let mut args = Vec::new();
+    let url_host = match &config.sni {
+        Some(name) if host.parse::<Ipv4Addr>().is_ok() => {
+            args.push("--connect-to".to_string());
+            args.push(format!("{}:{}:{}:{}", name, port, host, port));
+            name.clone()
+        }
+        _ => host.to_string(),
+    };
+    if config.insecure {
+        args.push("-k".to_string());
+    }
+    (url_host, args)
+}
 
 /*  获取一个信号量，如果获取失败，就会产生一个panic */
-pub async fn acquire_semaphore(semaphore: &Arc<Semaphore>) -> SemaphorePermit {
+pub async fn acquire_semaphore(semaphore: &Arc<Semaphore>) -> SemaphorePermit<'_> {
     semaphore.acquire().await.expect("Semaphore acquire failed")
 }
 
 /* 运行curl命令，获取响应时间，响应码，服务器环境信息，CF-RAY参数的值，Location参数的值，JetBrains License Server参数的值 */
 pub async fn run_curl(
-    ip: String,
+    target: Target,
     port: u16,
-    data_center_locations: Vec<DataCenterLocations>,
-) -> Result<(String, u16, String, u16, String, String, String, String), CustomError> {
-    let ip_type = determine_ipaddress_type(&ip);
-    let url = format!(
-        "http://{}{}",
-        ip,
-        match ip_type {
-            "Domain Name" => "".to_owned(),
-            _ => format!(":{}", port),
-        }
-    );
-    let print_address = if ip_type == "Domain Name" {
-        ip.clone()
+    source: Option<String>,
+    data_center_locations: Arc<Vec<DataCenterLocations>>,
+    config: Arc<Config>,
+) -> Result<ProbeResult, CustomError> {
+    let ip = target.host.clone();
+    let path = target
+        .path
+        .clone()
+        .or_else(|| config.path.clone())
+        .unwrap_or_default();
+    let scheme = target.scheme.unwrap_or(config.scheme).for_port(port);
+    let print_address = match &source {
+        Some(source) => format!(
+            "{}{} (source {})",
+            host_port(&ip, scheme, port),
+            path,
+            source
+        ),
+        None => format!("{}{}", host_port(&ip, scheme, port), path),
+    };
+    let (url_host, endpoint_args) = endpoint(&ip, port, &config);
+    let host_port = host_port(&url_host, scheme, port);
+    let request = Request::new(
+        format!("{}://{}{}", scheme, host_port, path),
+        &target,
+        endpoint_args,
+        source,
+        &config,
+    )
+    .inspect_err(|err| warn!("{}", err))?;
+
+    let (response, latency_stats) = if config.is_benchmark() {
+        run_benchmark(&request, &print_address, &config).await?
     } else {
-        format!("{}:{}", ip, port)
+        (
+            run_with_retries(&request, &print_address, &config).await?,
+            None,
+        )
     };
 
-    let start_time = Instant::now();
+    // 从curl命令的输出中，获取需要的参数值
+    let vec = response.headers;
+    if vec.len() != 3 {
+        return Err(CustomError::HttpParseError {
+            target: request.url.clone(),
+            reason: "Unexpected number of headers".to_string(),
+        });
+    }
+
+    // HTTP响应报头中，Server参数的值(服务器环境信息)
+    let server_env = vec[0].clone();
 
-    for retry_count in 0..MAX_RETRIES {
-        let request_start_time = Instant::now();
-        let result = timeout(
-            REQUEST_TIMEOUT,
-            tokio::process::Command::new("curl")
-                .arg("-I")
-                .arg(&url)
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .output(),
+    // HTTP响应报头中，CF-RAY参数的值
+    let cf_ray = vec[1].clone();
+    // ———— 提取出cf_ray中的一个部分字符，即location部分
+    let mut location: String = cf_ray
+        .split('-')
+        .nth(1)
+        .unwrap_or("")
+        .to_string()
+        .to_uppercase();
+
+    // 经过Cloudflare的目标，请求/cdn-cgi/trace，其中的colo比cf-ray更可靠
+    let cf_trace = if config.cf_trace
+        && (!cf_ray.is_empty() || server_env.contains(&config.cloudflare_match))
+    {
+        fetch_cf_trace(
+            &format!("{}://{}", scheme, host_port),
+            &request.shared_args(),
+            &config,
         )
-        .await;
+        .await
+    } else {
+        None
+    };
+    if let Some(trace) = &cf_trace {
+        location = trace.colo.clone();
+    }
+    // ———— 使用location值来查询国家代码（即查找locations.json文件）
+    let country_code = find_cca2(&data_center_locations, &location).unwrap_or_default();
 
-        match result {
-            Ok(Ok(output)) => {
-                let elapsed_time = format!("{:.2}", request_start_time.elapsed().as_millis());
-                let stdout = str::from_utf8(&output.stdout).unwrap_or("");
-                let status_line = stdout.lines().next().unwrap_or("");
-                let status_code = status_line
-                    .split_whitespace()
-                    .nth(1)
-                    .unwrap_or("0")
-                    .parse::<u16>()
-                    .unwrap_or(0);
-
-                // 从curl命令的输出中，获取需要的参数值
-                let vec = get_parameters_from_curl(stdout);
-                if vec.len() != 3 {
-                    return Err(CustomError::UnexpectedError(
-                        "Unexpected number of headers".to_string(),
-                    ));
-                }
-                // 获取当前时间
-                let formatted_time = get_current_time();
-
-                // HTTP响应报头中，Server参数的值(服务器环境信息)
-                let server_env = vec[0].clone();
-
-                // HTTP响应报头中，CF-RAY参数的值
-                let cf_ray = vec[1].clone();
-                // ———— 提取出cf_ray中的一个部分字符，即location部分
-                let mut parts = cf_ray.split('-');
-                let _first_part = parts.next().unwrap_or("");
-                let location: String = parts.next().unwrap_or("").to_string().to_uppercase();
-                // ———— 使用location值来查询国家代码（即查找locations.json文件）
-                let country_code = match find_cca2(data_center_locations, &location) {
-                    Ok(cca2) => cca2.to_string(),
-                    Err(_) => "".to_string(),
-                };
+    // HTTP响应报头中，Location参数的值(是否含jetbrains_match，默认为account.jetbrains.com/fls-auth)，跟随跳转时也检查跳转链
+    let mut jetbrains_license_server = vec[2] == "true"
+        || response
+            .redirects
+            .iter()
+            .any(|hop| hop.url.to_lowercase().contains(&config.jetbrains_match));
 
-                // HTTP响应报头中，Location参数的值(是否含account.jetbrains.com/fls-auth)
-                let jetbrains_license_server = vec[2].clone();
+    // 开启验证时，只有ping.action的响应正确才算激活服务器
+    let license_server = if config.jetbrains_verify && jetbrains_license_server {
+        let info = verify_license_server(
+            &format!("{}://{}", scheme, host_port),
+            &request.shared_args(),
+            &config,
+        )
+        .await;
+        jetbrains_license_server = info.verified;
+        Some(info)
+    } else {
+        None
+    };
+    // https目标由curl通过ALPN协商HTTP/2，alt-svc宣告了可用的其他协议；开启后再单独尝试HTTP/3握手
+    let protocol = if config.protocols {
+        let alt_svc: Vec<&str> = response
+            .header_lines
+            .iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case("alt-svc"))
+            .map(|(_, value)| value.as_str())
+            .collect();
+        let h3 = if config.http3 && scheme == "https" {
+            Some(probe_http3(&request.url, &request.shared_args(), &config).await)
+        } else {
+            None
+        };
+        Some(ProtocolInfo {
+            h2: (scheme == "https").then(|| response.http_version == "2"),
+            http_version: response.http_version.clone(),
+            alt_svc: parse_alt_svc(&alt_svc.join(", ")),
+            h3,
+        })
+    } else {
+        None
+    };
+    let final_status = response
+        .redirects
+        .last()
+        .map(|hop| hop.status_code)
+        .unwrap_or(response.status_code);
 
-                println!(
-                    "{} {} -> Request successful, HTTP status code: {}, Response time: {}ms",
-                    formatted_time, print_address, status_code, elapsed_time
-                );
+    // 基准测试模式下，以中位数作为响应时间
+    let response_time = match &latency_stats {
+        Some(stats) => stats.median,
+        None => response.timings.total,
+    };
 
-                return Ok((
-                    ip,
-                    port,
-                    elapsed_time,
-                    status_code,
-                    location,
-                    country_code,
-                    server_env,
-                    jetbrains_license_server,
-                ));
-            }
-            Ok(Err(err)) => {
-                let formatted_time = get_current_time();
-                let retries_left = MAX_RETRIES - retry_count - 1;
+    Ok(ProbeResult {
+        address: ip,
+        port,
+        scheme: scheme.to_string(),
+        path,
+        tags: target.tags,
+        response_time,
+        status_code: response.status_code,
+        colo: location,
+        country_code,
+        server: server_env,
+        jetbrains_license_server,
+        latency_stats,
+        download_speed: None,
+        headers: response.header_lines,
+        body: response.body,
+        redirects: response.redirects,
+        final_url: response.final_url,
+        cf_trace,
+        final_status,
+        license_server,
+        protocol,
+        proxy: request.proxy,
+        source: request.source,
+    })
+}
 
-                println!(
-                    "{} {} -> Request failed, Requests remaining: {}",
-                    formatted_time, print_address, retries_left
-                );
+/* 普通模式：请求一次，超时就重试 */
+async fn run_with_retries(
+    request: &Request,
+    print_address: &str,
+    config: &Config,
+) -> Result<CurlResponse, CustomError> {
+    let start_time = Instant::now();
 
-                if retry_count >= MAX_RETRIES - 1 || start_time.elapsed() >= TOTAL_TIMEOUT {
-                    return Err(CustomError::CommandExecutionFailed(err.to_string()));
-                }
+    for retry_count in 0..config.retries {
+        match curl_once(request, config).await {
+            Ok(response) => {
+                debug!(
+                    "{} {} -> Request successful, HTTP status code: {}, Response time: {:.2}ms",
+                    get_current_time(),
+                    print_address,
+                    response.status_code,
+                    response.timings.total
+                );
+                return Ok(response);
             }
-            Err(_) => {
-                let formatted_time = get_current_time();
-                let retries_left = MAX_RETRIES - retry_count - 1;
+            Err(err) => {
+                // 域名解析、连接、TLS、响应解析失败时重试也没有用，只重试超时和curl执行出错
+                let retryable = matches!(
+                    err,
+                    CustomError::Timeout | CustomError::CommandExecutionFailed(_)
+                );
+                let retries_left = if retryable {
+                    config.retries - retry_count - 1
+                } else {
+                    0
+                };
+                let reason = match err {
+                    CustomError::Timeout => "timeout".to_string(),
+                    _ => format!("failed ({})", err),
+                };
 
-                println!(
-                    "{} {} -> Request timeout, Requests remaining: {}",
-                    formatted_time, print_address, retries_left
+                debug!(
+                    "{} {} -> Request {}, Requests remaining: {}",
+                    get_current_time(),
+                    print_address,
+                    reason,
+                    retries_left
                 );
 
-                if retry_count >= MAX_RETRIES - 1 || start_time.elapsed() >= TOTAL_TIMEOUT {
-                    return Err(CustomError::CommandExecutionFailed("请求超时".to_string()));
+                if retries_left == 0 || start_time.elapsed() >= config.total_timeout {
+                    return Err(err);
                 }
             }
         }
@@ -146,24 +456,355 @@ pub async fn run_curl(
     ))
 }
 
-/* 获取当前时间 */
-fn get_parameters_from_curl(headers: &str) -> Vec<String> {
+/* 基准测试模式：对同一个目标采样多次，失败的采样计入丢包，不做重试 */
+async fn run_benchmark(
+    request: &Request,
+    print_address: &str,
+    config: &Config,
+) -> Result<(CurlResponse, Option<LatencyStats>), CustomError> {
+    let attempts = config.samples as usize;
+    let mut first_response: Option<CurlResponse> = None;
+    let mut samples: Vec<Timings> = Vec::with_capacity(attempts);
+
+    for index in 0..attempts {
+        if index > 0 && config.sample_interval > 0 {
+            sleep(Duration::from_millis(config.sample_interval)).await;
+        }
+        match curl_once(request, config).await {
+            // 状态码为0说明curl没有拿到HTTP响应，同样算作丢包
+            Ok(response) if response.status_code != 0 => {
+                samples.push(response.timings);
+                if first_response.is_none() {
+                    first_response = Some(response);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let stats = LatencyStats::from_samples(&samples, attempts);
+    match (first_response, stats) {
+        (Some(response), Some(stats)) => {
+            debug!(
+                "{} {} -> Benchmark finished, HTTP status code: {}, median: {:.2}ms, p90: {:.2}ms, loss: {:.0}%",
+                get_current_time(),
+                print_address,
+                response.status_code,
+                stats.median,
+                stats.p90,
+                stats.loss
+            );
+            Ok((response, Some(stats)))
+        }
+        _ => {
+            debug!(
+                "{} {} -> Benchmark failed, all {} samples lost",
+                get_current_time(),
+                print_address,
+                attempts
+            );
+            Err(CustomError::CommandExecutionFailed(format!(
+                "{} 的{}次采样全部失败",
+                print_address, attempts
+            )))
+        }
+    }
+}
+
+/* curl -w 的格式：标记之后是各阶段的累计时间(秒)和最终的地址 */
+fn write_out_format() -> String {
+    format!(
+        "\n{} %{{time_namelookup}} %{{time_connect}} %{{time_appconnect}} %{{time_starttransfer}} %{{time_total}} %{{url_effective}}",
+        WRITE_OUT_MARKER
+    )
+}
+
+/* 执行一次curl命令，并解析状态码、响应头和计时信息 */
+async fn curl_once(request: &Request, config: &Config) -> Result<CurlResponse, CustomError> {
+    if request.method != "HEAD" {
+        return curl_get(request, config).await;
+    }
+
+    let result = timeout(
+        config.timeout,
+        tokio::process::Command::new("curl")
+            .arg("-I")
+            .args(redirect_args(config))
+            .args(request.curl_args())
+            .arg("-w")
+            .arg(write_out_format())
+            .arg(&request.url)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await;
+
+    let output = match result {
+        Ok(Ok(output)) => output,
+        Ok(Err(err)) => return Err(CustomError::CommandExecutionFailed(err.to_string())),
+        Err(_) => return Err(CustomError::Timeout),
+    };
+
+    let stdout = str::from_utf8(&output.stdout).unwrap_or("");
+    let (headers, write_out) = stdout.split_once(WRITE_OUT_MARKER).unwrap_or((stdout, ""));
+
+    let response = build_response(&request.url, headers, write_out, None, config);
+    if response.status_code == 0 {
+        return Err(curl_failure(&request.url, output.status.code()));
+    }
+    Ok(response)
+}
+
+/* curl没有拿到HTTP响应时，按curl的退出码区分错误的类型（见 man curl 的 EXIT CODES） */
+fn curl_failure(url: &str, exit_code: Option<i32>) -> CustomError {
+    let target = url.to_string();
+    let Some(code) = exit_code else {
+        return CustomError::CommandExecutionFailed(format!("{} curl被终止", url));
+    };
+    let reason = format!("curl退出码{}", code);
+    match code {
+        5 | 6 => CustomError::DnsError { target, reason },
+        7 | 55 | 56 => CustomError::ConnectError { target, reason },
+        28 => CustomError::Timeout,
+        35 | 53 | 54 | 58 | 59 | 60 | 64 | 66 | 77 | 80 | 82 | 83 | 90 | 91 | 98 => {
+            CustomError::TlsError { target, reason }
+        }
+        // 1：HTTP/0.9等不支持的响应，8：无法识别的响应，52：空响应，16、92：HTTP/2帧错误
+        0 | 1 | 8 | 16 | 52 | 92 => CustomError::HttpParseError { target, reason },
+        _ => CustomError::CommandExecutionFailed(format!("{} {}", url, reason)),
+    }
+}
+
+/* 跟随跳转时的curl参数 */
+fn redirect_args(config: &Config) -> Vec<String> {
+    if config.follow_redirects {
+        vec![
+            "-L".to_string(),
+            "--max-redirs".to_string(),
+            config.max_redirects.to_string(),
+        ]
+    } else {
+        Vec::new()
+    }
+}
+
+/* 由curl输出的响应头和计时信息组成结果；跟随跳转时，状态码和响应头取第一个响应的，另外记录跳转链 */
+fn build_response(
+    url: &str,
+    headers: &str,
+    write_out: &str,
+    body: Option<BodyInfo>,
+    config: &Config,
+) -> CurlResponse {
+    let blocks = split_header_blocks(headers);
+    let first = blocks.first().map(String::as_str).unwrap_or("");
+    let (redirects, final_url) = if config.follow_redirects {
+        let redirects = build_chain(url, &blocks);
+        let final_url = match write_out.split_whitespace().nth(5) {
+            Some(effective) => Url::parse(effective)
+                .map(|effective| effective.to_string())
+                .unwrap_or_else(|_| effective.to_string()),
+            None => redirects
+                .last()
+                .map(|hop| hop.url.clone())
+                .unwrap_or_default(),
+        };
+        (redirects, final_url)
+    } else {
+        (Vec::new(), String::new())
+    };
+
+    CurlResponse {
+        status_code: parse_status_code(first),
+        headers: get_parameters_from_curl(first, &config.jetbrains_match),
+        header_lines: parse_header_lines(first),
+        timings: parse_timings(write_out),
+        body,
+        redirects,
+        final_url,
+        http_version: status_line_version(first),
+    }
+}
+
+/* 需要正文的请求：读取响应头和最多body_limit字节的正文，超过上限就结束curl，不再下载剩下的部分；
+只有GET模式才记录正文的摘要 */
+async fn curl_get(request: &Request, config: &Config) -> Result<CurlResponse, CustomError> {
+    let start_time = Instant::now();
+    let mut child = tokio::process::Command::new("curl")
+        .arg("-s")
+        .arg("-i")
+        .args(redirect_args(config))
+        .args(request.curl_args())
+        .arg("-w")
+        .arg(format!("%{{stderr}}{}", write_out_format()))
+        .arg(&request.url)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|err| CustomError::CommandExecutionFailed(err.to_string()))?;
+    let (Some(stdout), Some(mut stderr)) = (child.stdout.take(), child.stderr.take()) else {
+        return Err(CustomError::CommandExecutionFailed(
+            "无法读取curl的输出".to_string(),
+        ));
+    };
+
+    let (output, truncated) =
+        match timeout(config.timeout, read_limited(stdout, config.body_limit)).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(err)) => return Err(CustomError::CommandExecutionFailed(err.to_string())),
+            Err(_) => return Err(CustomError::Timeout),
+        };
+
+    // 提前结束的curl不会输出计时信息，只能用自己统计的总耗时
+    let mut exit_code: Option<i32> = None;
+    let write_out = if truncated {
+        let _ = child.kill().await;
+        String::new()
+    } else {
+        let mut stderr_text = String::new();
+        let remaining = config.timeout.saturating_sub(start_time.elapsed());
+        let _ = timeout(remaining, stderr.read_to_string(&mut stderr_text)).await;
+        exit_code = child.wait().await.ok().and_then(|status| status.code());
+        stderr_text
+            .split_once(WRITE_OUT_MARKER)
+            .map(|(_, write_out)| write_out.to_string())
+            .unwrap_or_default()
+    };
+
+    let body_start = find_body_start(&output).unwrap_or(output.len());
+    let headers = String::from_utf8_lossy(&output[..body_start]);
+    let body = &output[body_start..];
+    let body = &body[..body.len().min(config.body_limit)];
+
+    let mut response = build_response(
+        &request.url,
+        &headers,
+        &write_out,
+        config
+            .body
+            .then(|| BodyInfo::new(body, truncated, &config.body_match)),
+        config,
+    );
+    if response.status_code == 0 {
+        return Err(curl_failure(&request.url, exit_code));
+    }
+    if truncated {
+        response.timings.total = start_time.elapsed().as_secs_f64() * 1000.0;
+    }
+    Ok(response)
+}
+
+/* 读取curl的输出，正文超过limit字节时停止读取；返回(输出, 是否被截断) */
+async fn read_limited(
+    mut reader: impl AsyncRead + Unpin,
+    limit: usize,
+) -> std::io::Result<(Vec<u8>, bool)> {
+    let mut output = Vec::new();
+    let mut chunk = [0u8; 8192];
+    loop {
+        let count = reader.read(&mut chunk).await?;
+        if count == 0 {
+            return Ok((output, false));
+        }
+        output.extend_from_slice(&chunk[..count]);
+        if let Some(body_start) = find_body_start(&output) {
+            if output.len() - body_start > limit {
+                return Ok((output, true));
+            }
+        }
+    }
+}
+
+/* 最后一段响应头之后的空行的位置，即正文的起点；跟随跳转时前面会有多段响应头 */
+fn find_body_start(output: &[u8]) -> Option<usize> {
+    let mut offset = 0;
+    loop {
+        let rest = &output[offset..];
+        let end = rest
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .map(|index| index + 4)
+            .or_else(|| {
+                rest.windows(2)
+                    .position(|window| window == b"\n\n")
+                    .map(|index| index + 2)
+            })?;
+        offset += end;
+        if !output[offset..].starts_with(b"HTTP/") {
+            return Some(offset);
+        }
+    }
+}
+
+/* 从状态行中获取HTTP状态码，没有响应时为0 */
+fn parse_status_code(headers: &str) -> u16 {
+    headers
+        .lines()
+        .next()
+        .unwrap_or("")
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or("0")
+        .parse::<u16>()
+        .unwrap_or(0)
+}
+
+/* 解析curl -w输出的累计时间(秒)，换算成各阶段的耗时(毫秒) */
+fn parse_timings(write_out: &str) -> Timings {
+    let values: Vec<f64> = write_out
+        .split_whitespace()
+        .take(5)
+        .map(|value| value.parse::<f64>().unwrap_or(0.0) * 1000.0)
+        .collect();
+    if values.len() != 5 {
+        return Timings::default();
+    }
+    let (namelookup, connect, appconnect, starttransfer, total) =
+        (values[0], values[1], values[2], values[3], values[4]);
+
+    // 非TLS请求的appconnect为0，此时连接建立的时间点就是connect
+    let established = if appconnect > 0.0 {
+        appconnect
+    } else {
+        connect
+    };
+
+    Timings {
+        dns: namelookup,
+        connect: (connect - namelookup).max(0.0),
+        tls: (appconnect - connect).max(0.0),
+        ttfb: (starttransfer - established).max(0.0),
+        total,
+    }
+}
+
+/* 解析状态行之后的全部响应头，保留原始大小写和顺序，遇到空行结束 */
+fn parse_header_lines(headers: &str) -> Vec<(String, String)> {
+    headers
+        .lines()
+        .skip(1)
+        .take_while(|line| !line.trim().is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+/* 从响应头中获取Server、CF-RAY的值，以及Location是否指向JetBrains的激活服务 */
+fn get_parameters_from_curl(headers: &str, jetbrains_match: &str) -> Vec<String> {
     let mut header_map = HashMap::new();
 
     for line in headers.lines() {
         let line = line.to_lowercase();
-        if line.starts_with("server:") {
-            let value = line.splitn(2, ": ").nth(1).unwrap_or("");
-            let first_part = value.splitn(2, ' ').nth(0).unwrap_or("").to_string();
+        if let Some(value) = line.strip_prefix("server:") {
+            let first_part = value.trim().split(' ').next().unwrap_or("").to_string();
             header_map.insert("server", first_part);
-        } else if line.starts_with("cf-ray:") {
-            header_map.insert(
-                "cf-ray",
-                line.splitn(2, ": ").nth(1).unwrap_or("").to_string(),
-            );
-        } else if line.starts_with("location:") {
-            let value = line.splitn(2, ": ").nth(1).unwrap_or("");
-            if value.contains("account.jetbrains.com/fls-auth") {
+        } else if let Some(value) = line.strip_prefix("cf-ray:") {
+            header_map.insert("cf-ray", value.trim().to_string());
+        } else if let Some(value) = line.strip_prefix("location:") {
+            if value.contains(jetbrains_match) {
                 header_map.insert("licenseServer", "true".to_string());
             } else {
                 header_map.insert("licenseServer", "false".to_string());
@@ -171,12 +812,9 @@ fn get_parameters_from_curl(headers: &str) -> Vec<String> {
         }
     }
 
-    let server = header_map.get("server").unwrap_or(&"".to_string()).clone();
-    let cf_ray = header_map.get("cf-ray").unwrap_or(&"".to_string()).clone();
-    let jetbrains_license_server = header_map
-        .get("licenseServer")
-        .unwrap_or(&"".to_string())
-        .clone();
+    let server = header_map.get("server").cloned().unwrap_or_default();
+    let cf_ray = header_map.get("cf-ray").cloned().unwrap_or_default();
+    let jetbrains_license_server = header_map.get("licenseServer").cloned().unwrap_or_default();
 
     vec![server, cf_ray, jetbrains_license_server]
 }
@@ -194,3 +832,59 @@ pub async fn is_curl_installed() -> bool {
         Err(_) => false,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_body_after_headers() {
+        let output = b"HTTP/1.1 200 OK\r\nServer: x\r\n\r\n<html></html>";
+        assert_eq!(
+            &output[find_body_start(output).unwrap()..],
+            b"<html></html>"
+        );
+        let output = b"HTTP/1.1 200 OK\nServer: x\n\nbody";
+        assert_eq!(&output[find_body_start(output).unwrap()..], b"body");
+    }
+
+    #[test]
+    fn skips_redirect_and_interim_headers() {
+        let output = b"HTTP/1.1 301 Moved\r\nLocation: /a\r\n\r\n\
+            HTTP/1.1 100 Continue\r\n\r\n\
+            HTTP/2 200\r\nserver: x\r\n\r\nfinal";
+        assert_eq!(&output[find_body_start(output).unwrap()..], b"final");
+    }
+
+    #[test]
+    fn handles_empty_or_truncated_output() {
+        let output = b"HTTP/1.1 204 No Content\r\n\r\n";
+        assert_eq!(find_body_start(output), Some(output.len()));
+        assert_eq!(find_body_start(b"HTTP/1.1 200 OK\r\nServer: x\r\n"), None);
+        assert_eq!(find_body_start(b""), None);
+    }
+
+    #[test]
+    fn uses_sni_only_for_ip_targets() {
+        let config = Config {
+            sni: Some("example.com".to_string()),
+            ..Config::default()
+        };
+        let (host, args) = endpoint("1.1.1.1", 2053, &config);
+        assert_eq!(host, "example.com");
+        assert_eq!(args, ["--connect-to", "example.com:2053:1.1.1.1:2053"]);
+
+        let (host, args) = endpoint("other.org", 443, &config);
+        assert_eq!(host, "other.org");
+        assert!(args.is_empty());
+
+        let config = Config {
+            insecure: true,
+            ..Config::default()
+        };
+        assert_eq!(
+            endpoint("1.1.1.1", 443, &config),
+            ("1.1.1.1".to_string(), vec!["-k".to_string()])
+        );
+    }
+}
@@ -0,0 +1,318 @@
+use futures::stream::{self, StreamExt};
+use log::info;
+use regex::Regex;
+use std::{collections::HashSet, path::Path, process::Stdio, str};
+use tokio::time::timeout;
+
+use crate::utils::{
+    common::delete_if_file_exists,
+    config::Config,
+    data::{host_port, parse_url, target_label, Target},
+    error::CustomError,
+    exclusion::allowed_targets,
+    http_request::{endpoint, ProbeResult},
+    proxy::{next_proxy, proxy_args},
+};
+
+// 激活服务器的心跳接口，会原样返回请求中的salt
+const PING_PATH: &str = "/rpc/ping.action";
+
+// 重新验证列表文件中的旧地址时，同时进行的请求数量
+const REVERIFY_CONCURRENCY: usize = 20;
+
+/* 激活服务器的验证结果 */
+#[derive(Debug, Clone, Default)]
+pub struct LicenseServerInfo {
+    pub verified: bool,  // ping.action 返回了OK并且原样返回了salt
+    pub version: String, // 响应中的版本号，没有时取Server响应头
+}
+
+/* 请求 "协议://地址:端口/rpc/ping.action?salt=随机数"，检查响应的格式 */
+pub async fn verify_license_server(
+    base_url: &str,
+    extra_args: &[String],
+    config: &Config,
+) -> LicenseServerInfo {
+    let salt: u32 = rand::random();
+    let output = timeout(
+        config.timeout,
+        tokio::process::Command::new("curl")
+            .arg("-s")
+            .arg("-i")
+            .args(extra_args)
+            .arg(format!("{}{}?salt={}", base_url, PING_PATH, salt))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await;
+
+    match output {
+        Ok(Ok(output)) => parse_ping_response(&String::from_utf8_lossy(&output.stdout), salt),
+        _ => LicenseServerInfo::default(),
+    }
+}
+
+/* 解析ping.action的响应：<PingResponse>...<responseCode>OK</responseCode><salt>salt</salt>...</PingResponse> */
+fn parse_ping_response(response: &str, salt: u32) -> LicenseServerInfo {
+    let element = |name: &str| {
+        Regex::new(&format!(r"(?s)<{0}>\s*(.*?)\s*</{0}>", name))
+            .ok()
+            .and_then(|pattern| pattern.captures(response))
+            .and_then(|captures| captures.get(1))
+            .map(|value| value.as_str().to_string())
+    };
+
+    let verified = response.contains("<PingResponse")
+        && element("responseCode").as_deref() == Some("OK")
+        && element("salt") == Some(salt.to_string());
+    if !verified {
+        return LicenseServerInfo::default();
+    }
+
+    let version = ["serverVersion", "version", "buildNumber"]
+        .iter()
+        .find_map(|name| element(name))
+        .or_else(|| {
+            response
+                .lines()
+                .take_while(|line| !line.trim().is_empty())
+                .filter_map(|line| line.split_once(':'))
+                .find(|(name, _)| name.trim().eq_ignore_ascii_case("server"))
+                .map(|(_, value)| value.trim().to_string())
+        })
+        .unwrap_or_default();
+
+    LicenseServerInfo { verified, version }
+}
+
+/* 激活服务器列表中的一行：以目标的URL（协议://地址:端口）开头，去重和重新验证都依据开头的URL；
+模板不以 {url} 开头时，在前面加上URL和一个空格 */
+pub fn license_server_line(result: &ProbeResult, config: &Config) -> String {
+    let url = result.base_url();
+    match &config.list_template {
+        Some(template) if template.starts_with_url() => template.render(result),
+        Some(template) => format!("{} {}", url, template.render(result))
+            .trim_end()
+            .to_string(),
+        None => url,
+    }
+}
+
+/* 列表中一行的地址部分，"#"和空白之后是附加信息；同一地址只保留一行 */
+fn entry_key(line: &str) -> &str {
+    line.split(|c: char| c == '#' || c.is_whitespace())
+        .next()
+        .unwrap_or("")
+}
+
+/* 列表中的URL对应的目标；旧版本写入的行只有 地址:端口，不知道原来的协议，返回None */
+fn entry_target(key: &str) -> Option<Target> {
+    if !key.contains("://") {
+        return None;
+    }
+    parse_url(key).ok()
+}
+
+/* 目标的基础URL，与 ProbeResult::base_url 的写法一致 */
+fn target_base_url(target: &Target) -> Option<String> {
+    let scheme = target.scheme?.for_port(target.port?);
+    Some(format!(
+        "{}://{}",
+        scheme,
+        host_port(&target.host, scheme, target.port?)
+    ))
+}
+
+/* 依次从每个源地址验证，任意一个通过就算有效；没有指定source时直接请求 */
+async fn reverify_entry(target: &Target, config: &Config) -> bool {
+    let (Some(scheme), Some(port)) = (target.scheme, target.port) else {
+        return false;
+    };
+    let scheme = scheme.for_port(port);
+    // 与扫描时一样，按sni和insecure连接
+    let (url_host, endpoint_args) = endpoint(&target.host, port, config);
+    let base_url = format!("{}://{}", scheme, host_port(&url_host, scheme, port));
+
+    let sources: Vec<Option<&String>> = if config.sources.is_empty() {
+        vec![None]
+    } else {
+        config.sources.iter().map(Some).collect()
+    };
+    for source in sources {
+        let mut args = proxy_args(next_proxy(config), config);
+        args.extend(endpoint_args.iter().cloned());
+        if let Some(source) = source {
+            args.push("--interface".to_string());
+            args.push(source.clone());
+        }
+        if verify_license_server(&base_url, &args, config)
+            .await
+            .verified
+        {
+            return true;
+        }
+    }
+    false
+}
+
+/* 重新验证旧地址，返回保留的行和其中没有重新验证的行数。只验证以URL记录、并且不在排除列表、
+保留地址段和禁止扫描列表中的地址，失效的剔除；其余的无法按原来的端点验证，原样保留 */
+async fn reverify_entries(
+    entries: Vec<String>,
+    config: &Config,
+) -> Result<(Vec<String>, usize), CustomError> {
+    let targets: Vec<Target> = entries
+        .iter()
+        .filter_map(|line| entry_target(entry_key(line)))
+        .collect();
+    let allowed: HashSet<String> = allowed_targets(targets, config)
+        .await?
+        .iter()
+        .filter_map(target_base_url)
+        .collect();
+
+    let checked: Vec<(Option<String>, bool)> = stream::iter(entries)
+        .map(|line| {
+            let allowed = &allowed;
+            async move {
+                let target = entry_target(entry_key(&line)).filter(|target| {
+                    target_base_url(target).is_some_and(|base_url| allowed.contains(&base_url))
+                });
+                match target {
+                    Some(target) => {
+                        let verified = reverify_entry(&target, config).await;
+                        (verified.then_some(line), true)
+                    }
+                    None => (Some(line), false),
+                }
+            }
+        })
+        .buffered(REVERIFY_CONCURRENCY)
+        .collect()
+        .await;
+
+    let unchecked = checked.iter().filter(|(_, verified)| !verified).count();
+    let kept = checked.into_iter().filter_map(|(line, _)| line).collect();
+    Ok((kept, unchecked))
+}
+
+/* 更新激活服务器列表文件：本次的地址排在前面，旧地址去重后保留；开启验证时，旧地址重新验证，失效的剔除 */
+pub async fn update_license_server_list(
+    file_path: &str,
+    new_entries: &[String],
+    config: &Config,
+) -> Result<(), CustomError> {
+    let existing: Vec<String> = if Path::new(file_path).exists() {
+        std::fs::read_to_string(file_path)
+            .map_err(|err| CustomError::output(file_path, err))?
+            .lines()
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    // 同一个URL只保留一行；旧版本写入的 地址:端口 与某个URL的地址相同时，也视为重复
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut labels: HashSet<String> = HashSet::new();
+    let mut is_first = |line: &String| {
+        let key = entry_key(line);
+        match entry_target(key) {
+            Some(target) => {
+                labels.insert(target_label(&target.host, target.port.unwrap_or(80)));
+                seen.insert(key.to_string())
+            }
+            None => !labels.contains(key) && seen.insert(key.to_string()),
+        }
+    };
+    let new_entries: Vec<String> = new_entries
+        .iter()
+        .filter(|line| is_first(line))
+        .cloned()
+        .collect();
+    let old_entries: Vec<String> = existing.into_iter().filter(|line| is_first(line)).collect();
+    let old_count = old_entries.len();
+
+    let (old_entries, unchecked) = if config.jetbrains_verify {
+        reverify_entries(old_entries, config).await?
+    } else {
+        (old_entries, 0)
+    };
+
+    if unchecked > 0 {
+        info!(
+            "JetBrains激活服务器列表：{}个旧地址没有记录协议，或者在排除列表、保留地址段、禁止扫描列表中，未重新验证",
+            unchecked
+        );
+    }
+    info!(
+        "JetBrains激活服务器列表：本次{}个，保留旧地址{}个，剔除失效的旧地址{}个",
+        new_entries.len(),
+        old_entries.len(),
+        old_count - old_entries.len()
+    );
+
+    let lines: Vec<String> = new_entries.into_iter().chain(old_entries).collect();
+    if lines.is_empty() {
+        delete_if_file_exists(file_path).map_err(|err| CustomError::output(file_path, err))?;
+        return Ok(());
+    }
+    std::fs::write(file_path, lines.join("\n") + "\n")
+        .map_err(|err| CustomError::output(file_path, err))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESPONSE: &str = "HTTP/1.1 200 OK\r\nServer: jetty\r\nContent-Type: text/xml\r\n\r\n\
+        <!-- abc --><PingResponse><message></message><responseCode>OK</responseCode>\
+        <salt>42</salt><serverVersion>1.2.3</serverVersion></PingResponse>";
+
+    #[test]
+    fn verifies_ok_response_with_matching_salt() {
+        let info = parse_ping_response(RESPONSE, 42);
+        assert!(info.verified);
+        assert_eq!(info.version, "1.2.3");
+    }
+
+    #[test]
+    fn falls_back_to_server_header_for_version() {
+        let response = "HTTP/1.1 200 OK\r\nserver: ls/2.0\r\n\r\n\
+            <PingResponse><responseCode>OK</responseCode><salt>7</salt></PingResponse>";
+        let info = parse_ping_response(response, 7);
+        assert!(info.verified);
+        assert_eq!(info.version, "ls/2.0");
+    }
+
+    #[test]
+    fn rejects_wrong_salt_code_or_format() {
+        assert!(!parse_ping_response(RESPONSE, 43).verified);
+        assert!(!parse_ping_response(&RESPONSE.replace(">OK<", ">ERROR<"), 42).verified);
+        assert!(!parse_ping_response("HTTP/1.1 200 OK\r\n\r\n<html>OK 42</html>", 42).verified);
+        assert!(!parse_ping_response("", 42).verified);
+    }
+
+    #[test]
+    fn keeps_scheme_and_port_of_entries() {
+        let base_url = |line: &str| entry_target(entry_key(line)).and_then(|t| target_base_url(&t));
+        assert_eq!(
+            base_url("https://example.com #HKG").as_deref(),
+            Some("https://example.com")
+        );
+        assert_eq!(
+            base_url("http://example.com:443").as_deref(),
+            Some("http://example.com:443")
+        );
+        assert_eq!(
+            base_url("https://1.2.3.4:8443#x").as_deref(),
+            Some("https://1.2.3.4:8443")
+        );
+        assert_eq!(base_url("1.2.3.4:80"), None);
+        assert_eq!(base_url("example.com"), None);
+    }
+}
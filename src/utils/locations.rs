@@ -1,8 +1,11 @@
+use log::{info, warn};
 use reqwest::Client;
 use serde::Deserialize;
-use std::{error::Error, io::Read, path::Path};
+use std::{io::Read, path::Path};
 use tokio::io::AsyncWriteExt;
 
+use crate::utils::error::CustomError;
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct DataCenterLocations {
     #[serde(rename = "iata")]
@@ -20,59 +23,64 @@ pub struct DataCenterLocations {
 }
 
 /* 读取locations.json文件，并解析 JSON 数据 */
-pub fn load_location_file(file_path: &str) -> Result<Vec<DataCenterLocations>, Box<dyn Error>> {
-    let mut file = std::fs::File::open(file_path)?;
+pub fn load_location_file(file_path: &str) -> Result<Vec<DataCenterLocations>, CustomError> {
+    let to_error = |err: std::io::Error| CustomError::location_file(file_path, err);
+    let mut file = std::fs::File::open(file_path).map_err(to_error)?;
 
     let mut json_data = String::new();
-    file.read_to_string(&mut json_data)?;
+    file.read_to_string(&mut json_data).map_err(to_error)?;
 
     // 解析 JSON 数据
-    let data_center_locations: Vec<DataCenterLocations> = serde_json::from_str(&json_data)?;
+    let data_center_locations: Vec<DataCenterLocations> = serde_json::from_str(&json_data)
+        .map_err(|err| CustomError::location_file(file_path, err))?;
     Ok(data_center_locations)
 }
 
+/* 查找匹配的 iata，并返回 cca2；没有找到时为None */
 pub fn find_cca2(
-    data_center_locations: Vec<DataCenterLocations>,
+    data_center_locations: &[DataCenterLocations],
     target_iata: &str,
-) -> Result<String, Box<dyn Error>> {
-    // 查找匹配的 iata，并返回 cca2
-    for data_center_location in data_center_locations {
-        if data_center_location.iata == target_iata {
-            return Ok(data_center_location.cca2);
-        }
-    }
-    // 没有找到就返回空字符串
-    Err("".into())
+) -> Option<String> {
+    data_center_locations
+        .iter()
+        .find(|data_center_location| data_center_location.iata == target_iata)
+        .map(|data_center_location| data_center_location.cca2.clone())
 }
 
 /* 如果文件不存在，则从网上下载 */
 pub async fn check_and_download_location_file(
     file_path: &str,
     url: &str,
-) -> Result<(), Box<dyn Error>> {
+) -> Result<(), CustomError> {
     if !Path::new(file_path).exists() {
-        println!("{} 文件不存在。准备从网上下载...", file_path);
+        info!("{} 文件不存在。准备从网上下载...", file_path);
 
         // 创建一个HTTP客户端
         let client = Client::new();
 
         // 异步下载
-        let response = client.get(url).send().await?;
+        let to_error = |err: reqwest::Error| CustomError::location_file(file_path, err);
+        let response = client.get(url).send().await.map_err(to_error)?;
 
         if response.status().is_success() {
             // 异步读取响应文本
-            let content = response.text().await?;
+            let content = response.text().await.map_err(to_error)?;
 
             // 将内容写入文件
-            let mut file = tokio::fs::File::create(file_path).await?;
-            file.write_all(content.as_bytes()).await?;
+            let write_error = |err: std::io::Error| CustomError::location_file(file_path, err);
+            let mut file = tokio::fs::File::create(file_path)
+                .await
+                .map_err(write_error)?;
+            file.write_all(content.as_bytes())
+                .await
+                .map_err(write_error)?;
 
-            println!("文件下载并保存为 {}\n", file_path);
+            info!("文件下载并保存为 {}", file_path);
         } else {
-            eprintln!("下载文件失败：HTTP {}\n", response.status());
+            warn!("下载文件失败：HTTP {}", response.status());
         }
     } else {
-        println!("{} 文件已经存在。\n", file_path);
+        info!("{} 文件已经存在。", file_path);
     }
 
     Ok(())
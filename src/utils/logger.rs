@@ -0,0 +1,136 @@
+use chrono::Local;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde::Deserialize;
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    sync::Mutex,
+};
+
+use crate::utils::{config::Config, error::CustomError};
+
+/* 日志级别，越往后输出得越多 */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    pub fn to_filter(self) -> LevelFilter {
+        match self {
+            LogLevel::Error => LevelFilter::Error,
+            LogLevel::Warn => LevelFilter::Warn,
+            LogLevel::Info => LevelFilter::Info,
+            LogLevel::Debug => LevelFilter::Debug,
+            LogLevel::Trace => LevelFilter::Trace,
+        }
+    }
+}
+
+/* 日志格式：text是给人看的文字，json每行一个JSON对象，便于其他程序收集 */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+/* 输出到标准错误，另外可以同时写入日志文件；只记录本程序的日志，不记录依赖库的 */
+struct Logger {
+    level: LevelFilter,
+    format: LogFormat,
+    file: Option<Mutex<File>>,
+}
+
+impl Logger {
+    /* 标准错误中的一行：text格式只在警告和错误前加上级别 */
+    fn console_line(&self, record: &Record) -> String {
+        match self.format {
+            LogFormat::Json => json_line(record),
+            LogFormat::Text => match record.level() {
+                Level::Error => format!("错误：{}", record.args()),
+                Level::Warn => format!("警告：{}", record.args()),
+                _ => record.args().to_string(),
+            },
+        }
+    }
+
+    /* 日志文件中的一行：text格式带上时间和级别 */
+    fn file_line(&self, record: &Record) -> String {
+        match self.format {
+            LogFormat::Json => json_line(record),
+            LogFormat::Text => format!(
+                "{} {:<5} {}",
+                Local::now().format("%Y/%m/%d %H:%M:%S%.3f"),
+                record.level(),
+                record.args()
+            ),
+        }
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level && metadata.target().starts_with(env!("CARGO_CRATE_NAME"))
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let _ = writeln!(io::stderr().lock(), "{}", self.console_line(record));
+        if let Some(file) = &self.file {
+            if let Ok(mut file) = file.lock() {
+                let _ = writeln!(file, "{}", self.file_line(record));
+            }
+        }
+    }
+
+    fn flush(&self) {
+        let _ = io::stderr().flush();
+        if let Some(file) = &self.file {
+            if let Ok(mut file) = file.lock() {
+                let _ = file.flush();
+            }
+        }
+    }
+}
+
+/* JSON格式的一行：时间、级别、模块、内容 */
+fn json_line(record: &Record) -> String {
+    serde_json::json!({
+        "time": Local::now().format("%Y-%m-%dT%H:%M:%S%.3f%:z").to_string(),
+        "level": record.level().as_str().to_lowercase(),
+        "target": record.target(),
+        "message": record.args().to_string(),
+    })
+    .to_string()
+}
+
+/* 按配置初始化日志，日志文件以追加的方式打开 */
+pub fn init_logger(config: &Config) -> Result<(), CustomError> {
+    let file = match &config.log_file {
+        Some(path) => Some(Mutex::new(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|err| CustomError::config(format!("打开日志文件'{}'失败", path), err))?,
+        )),
+        None => None,
+    };
+
+    log::set_boxed_logger(Box::new(Logger {
+        level: config.log_level,
+        format: config.log_format,
+        file,
+    }))
+    .map_err(|err| CustomError::UnexpectedError(err.to_string()))?;
+    log::set_max_level(config.log_level);
+    Ok(())
+}
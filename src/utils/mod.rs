@@ -1,5 +1,25 @@
+pub mod args;
+pub mod benchmark;
+pub mod cf_trace;
 pub mod common;
+pub mod config;
 pub mod data;
 pub mod error;
+pub mod exclusion;
+pub mod filter;
+pub mod fingerprint;
 pub mod http_request;
+pub mod jetbrains;
 pub mod locations;
+pub mod logger;
+pub mod order;
+pub mod output;
+pub mod ports;
+pub mod progress;
+pub mod protocol;
+pub mod proxy;
+pub mod redirect;
+pub mod sampling;
+pub mod speed_test;
+pub mod tcp_check;
+pub mod template;
@@ -0,0 +1,148 @@
+use clap::ValueEnum;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use crate::utils::{data::Target, sampling::subnet_24};
+
+/* 扫描目标的顺序 */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Order {
+    /// 随机打乱
+    Random,
+    /// 按/24网段轮流取目标，分散对同一网段的压力（网段内的顺序随机）
+    RoundRobin,
+    /// 保持输入中的顺序
+    Sequential,
+}
+
+impl Order {
+    /* 该顺序是否用到随机种子 */
+    pub fn uses_seed(&self) -> bool {
+        !matches!(self, Order::Sequential)
+    }
+}
+
+/* 按指定的方式排列目标，相同的种子总是得到相同的顺序 */
+pub fn order_targets(targets: Vec<Target>, order: Order, seed: u64) -> Vec<Target> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    match order {
+        Order::Random => {
+            let mut targets = targets;
+            targets.shuffle(&mut rng);
+            targets
+        }
+        Order::RoundRobin => round_robin(targets, &mut rng),
+        Order::Sequential => targets,
+    }
+}
+
+/* 按/24网段分组（域名各自一组），组内打乱后轮流从每组取一个 */
+fn round_robin(targets: Vec<Target>, rng: &mut StdRng) -> Vec<Target> {
+    let total = targets.len();
+    let mut group_index: HashMap<String, usize> = HashMap::new();
+    let mut groups: Vec<Vec<Target>> = Vec::new();
+    for target in targets {
+        let key = match subnet_24(&target.host) {
+            Some([a, b, c]) => format!("{}.{}.{}", a, b, c),
+            None => target.host.clone(),
+        };
+        let index = *group_index.entry(key).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[index].push(target);
+    }
+
+    let mut groups: Vec<std::vec::IntoIter<Target>> = groups
+        .into_iter()
+        .map(|mut group| {
+            group.shuffle(rng);
+            group.into_iter()
+        })
+        .collect();
+    groups.shuffle(rng);
+
+    let mut ordered = Vec::with_capacity(total);
+    while ordered.len() < total {
+        for group in groups.iter_mut() {
+            if let Some(target) = group.next() {
+                ordered.push(target);
+            }
+        }
+    }
+    ordered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn targets(hosts: &[&str]) -> Vec<Target> {
+        hosts
+            .iter()
+            .map(|host| Target::new(host.to_string()))
+            .collect()
+    }
+
+    fn hosts(targets: &[Target]) -> Vec<String> {
+        targets.iter().map(|target| target.host.clone()).collect()
+    }
+
+    fn subnet_hosts() -> Vec<Target> {
+        let mut hosts = Vec::new();
+        for subnet in 1..=3 {
+            for last in 1..=4 {
+                hosts.push(format!("10.0.{}.{}", subnet, last));
+            }
+        }
+        hosts.push("example.com".to_string());
+        hosts.into_iter().map(Target::new).collect()
+    }
+
+    #[test]
+    fn same_seed_gives_same_order() {
+        for order in [Order::Random, Order::RoundRobin] {
+            let mut first = hosts(&order_targets(subnet_hosts(), order, 42));
+            let second = hosts(&order_targets(subnet_hosts(), order, 42));
+            assert_eq!(first, second);
+
+            // 不同的种子得到不同的顺序，但目标不变
+            let mut other = hosts(&order_targets(subnet_hosts(), order, 43));
+            assert_ne!(first, other);
+            first.sort();
+            other.sort();
+            assert_eq!(first, other);
+        }
+    }
+
+    #[test]
+    fn sequential_keeps_input_order() {
+        let input = ["b.com", "1.1.1.1", "a.com"];
+        let ordered = order_targets(targets(&input), Order::Sequential, 7);
+        assert_eq!(hosts(&ordered), input);
+    }
+
+    #[test]
+    fn round_robin_interleaves_subnets() {
+        for seed in 0..20 {
+            let ordered = order_targets(subnet_hosts(), Order::RoundRobin, seed);
+            assert_eq!(ordered.len(), 13);
+            let group = |target: &Target| match subnet_24(&target.host) {
+                Some([_, _, c]) => c.to_string(),
+                None => target.host.clone(),
+            };
+            // 每一轮依次从4个组（3个/24网段和1个域名）各取一个，第一轮之后域名用完
+            let groups: Vec<String> = ordered.iter().map(group).collect();
+            let mut first_round = groups[..4].to_vec();
+            first_round.sort();
+            assert_eq!(first_round, ["1", "2", "3", "example.com"]);
+            for round in groups[4..].chunks(3) {
+                let mut round = round.to_vec();
+                round.sort();
+                assert_eq!(round, ["1", "2", "3"]);
+            }
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use crate::utils::{config::Config, http_request::ProbeResult, redirect::format_chain};
+
+// CSV最后一行的说明，读取之前输出的CSV时据此跳过这一行
+pub const NOTE_MARKER: &str = "注意：已经剔除无效数据";
+
+/* CSV文件包含哪些可选的列，由配置和扫描结果共同决定 */
+#[derive(Debug, Clone)]
+pub struct CsvLayout {
+    benchmark: bool,             // 基准测试的统计列
+    speed: bool,                 // 下载速度列
+    path: bool,                  // 有目标指定了请求路径
+    tags: bool,                  // 有目标带了行内标签
+    body: bool,                  // GET模式下的正文摘要列
+    redirects: bool,             // 跳转链、最终地址和最终状态码
+    cf_trace: bool,              // /cdn-cgi/trace 的信息
+    license_server: bool,        // 激活服务器的验证结果和版本
+    protocols: bool,             // 协议版本、HTTP/2、alt-svc
+    http3: bool,                 // HTTP/3握手的结果
+    proxy: bool,                 // 探测使用的代理
+    source: bool,                // 发出请求的源地址
+    header_columns: Vec<String>, // 单独输出的响应头
+    all_headers: bool,           // 全部响应头
+}
+
+impl CsvLayout {
+    pub fn new(config: &Config, results: &[ProbeResult]) -> Self {
+        CsvLayout {
+            benchmark: config.is_benchmark(),
+            speed: config.speed_test_top > 0,
+            path: results.iter().any(|result| !result.path.is_empty()),
+            tags: results.iter().any(|result| !result.tags.is_empty()),
+            body: config.body,
+            redirects: config.follow_redirects,
+            cf_trace: config.cf_trace,
+            license_server: config.jetbrains_verify,
+            protocols: config.protocols,
+            http3: config.http3,
+            proxy: !config.proxies.is_empty(),
+            source: !config.sources.is_empty(),
+            header_columns: config.header_column.clone(),
+            all_headers: config.all_headers,
+        }
+    }
+}
+
+/* CSV文件的标题，按布局追加可选的列 */
+pub fn csv_header(layout: &CsvLayout) -> Vec<String> {
+    let mut header = vec![
+        "网络地址",
+        "端口",
+        "协议",
+        "响应时间(ms)",
+        "HTTP状态码",
+        "数据中心",
+        "国家代码",
+        "服务器环境",
+    ];
+    if layout.benchmark {
+        header.extend([
+            "最小(ms)",
+            "中位数(ms)",
+            "P90(ms)",
+            "最大(ms)",
+            "抖动(ms)",
+            "丢包率(%)",
+            "DNS(ms)",
+            "连接(ms)",
+            "TLS(ms)",
+            "首字节(ms)",
+        ]);
+    }
+    if layout.speed {
+        header.push("下载速度(MB/s)");
+    }
+    if layout.path {
+        header.push("请求路径");
+    }
+    if layout.tags {
+        header.push("标签");
+    }
+    if layout.body {
+        header.extend(["标题", "正文长度", "SHA256", "MMH3", "指纹"]);
+    }
+    if layout.redirects {
+        header.extend(["跳转链", "最终地址", "最终状态码"]);
+    }
+    if layout.cf_trace {
+        header.extend(["出口IP", "访问者位置", "TLS版本", "HTTP版本", "WARP", "SNI"]);
+    }
+    if layout.license_server {
+        header.extend(["激活服务器验证", "激活服务器版本"]);
+    }
+    if layout.protocols {
+        header.extend(["协议版本", "HTTP/2", "Alt-Svc"]);
+    }
+    if layout.http3 {
+        header.push("HTTP/3");
+    }
+    if layout.proxy {
+        header.push("代理");
+    }
+    if layout.source {
+        header.push("源地址");
+    }
+    let mut header: Vec<String> = header.into_iter().map(String::from).collect();
+    header.extend(layout.header_columns.iter().cloned());
+    if layout.all_headers {
+        header.push("响应头".to_string());
+    }
+    header
+}
+
+/* 将一条探测结果转换为CSV的一行，列的顺序与csv_header一致 */
+pub fn csv_record(result: &ProbeResult, layout: &CsvLayout) -> Vec<String> {
+    let mut record = vec![
+        result.address.clone(),
+        result.port.to_string(),
+        result.scheme.clone(),
+        format!("{:.2}", result.response_time),
+        result.status_code.to_string(),
+        result.colo.clone(),
+        result.country_code.clone(),
+        result.server.clone(),
+    ];
+    if layout.benchmark {
+        let stats = result.latency_stats.unwrap_or_default();
+        record.extend(
+            [
+                stats.min,
+                stats.median,
+                stats.p90,
+                stats.max,
+                stats.jitter,
+                stats.loss,
+                stats.dns,
+                stats.connect,
+                stats.tls,
+                stats.ttfb,
+            ]
+            .iter()
+            .map(|value| format!("{:.2}", value)),
+        );
+    }
+    if layout.speed {
+        record.push(
+            result
+                .download_speed
+                .map(|speed| format!("{:.2}", speed))
+                .unwrap_or_default(),
+        );
+    }
+    if layout.path {
+        record.push(result.path.clone());
+    }
+    if layout.tags {
+        record.push(result.tags.join(" "));
+    }
+    if layout.body {
+        let body = result.body.clone().unwrap_or_default();
+        record.extend([
+            body.title,
+            // 达到字节上限时，实际的正文更长
+            format!("{}{}", body.length, if body.truncated { "+" } else { "" }),
+            body.sha256,
+            body.mmh3.to_string(),
+            body.matches.join(" "),
+        ]);
+    }
+    if layout.redirects {
+        record.extend([
+            format_chain(&result.redirects),
+            result.final_url.clone(),
+            result.final_status.to_string(),
+        ]);
+    }
+    if layout.cf_trace {
+        let trace = result.cf_trace.clone().unwrap_or_default();
+        record.extend([
+            trace.ip, trace.loc, trace.tls, trace.http, trace.warp, trace.sni,
+        ]);
+    }
+    if layout.license_server {
+        match &result.license_server {
+            Some(info) => record.extend([
+                if info.verified { "通过" } else { "失败" }.to_string(),
+                info.version.clone(),
+            ]),
+            None => record.extend([String::new(), String::new()]),
+        }
+    }
+    if layout.protocols || layout.http3 {
+        let protocol = result.protocol.clone().unwrap_or_default();
+        let support = |value: Option<bool>| match value {
+            Some(true) => "支持".to_string(),
+            Some(false) => "不支持".to_string(),
+            None => String::new(),
+        };
+        if layout.protocols {
+            record.extend([
+                protocol.http_version.clone(),
+                support(protocol.h2),
+                protocol.alt_svc.join(","),
+            ]);
+        }
+        if layout.http3 {
+            record.push(support(protocol.h3));
+        }
+    }
+    if layout.proxy {
+        record.push(
+            result
+                .proxy
+                .as_ref()
+                .map(|proxy| proxy.to_string())
+                .unwrap_or_default(),
+        );
+    }
+    if layout.source {
+        record.push(result.source.clone().unwrap_or_default());
+    }
+    for name in &layout.header_columns {
+        record.push(result.header(name).unwrap_or_default());
+    }
+    if layout.all_headers {
+        record.push(
+            result
+                .headers
+                .iter()
+                .map(|(name, value)| format!("{}: {}", name, value))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        );
+    }
+    record
+}
@@ -0,0 +1,180 @@
+use serde::{Deserialize, Deserializer};
+use std::{collections::HashSet, str::FromStr};
+
+// Cloudflare代理的HTTP端口
+const CF_HTTP_PORTS: &[u16] = &[80, 8080, 8880, 2052, 2082, 2086, 2095];
+
+// Cloudflare代理的HTTPS端口
+const CF_HTTPS_PORTS: &[u16] = &[443, 2053, 2083, 2087, 2096, 8443];
+
+// 端口集合中可以使用的预设名称
+const PRESETS: &[(&str, &[u16])] = &[("cf-http", CF_HTTP_PORTS), ("cf-https", CF_HTTPS_PORTS)];
+
+/* 常用的TLS端口，scheme为auto时这些端口使用https */
+pub fn is_tls_port(port: u16) -> bool {
+    CF_HTTPS_PORTS.contains(&port)
+}
+
+/* 端口集合，例如 "80,443,2052-2096,cf-https"，按出现的先后顺序去重 */
+#[derive(Debug, Clone, Default)]
+pub struct PortSet {
+    ports: Vec<u16>,
+    seen: HashSet<u16>, // 已经加入的端口，用于去重，避免大范围时逐个查找Vec
+}
+
+impl PortSet {
+    pub fn into_vec(self) -> Vec<u16> {
+        self.ports
+    }
+
+    fn push(&mut self, port: u16) {
+        if self.seen.insert(port) {
+            self.ports.push(port);
+        }
+    }
+
+    /* 加入一项：单个端口、端口范围或预设名称 */
+    fn add_item(&mut self, item: &str) -> Result<(), String> {
+        let item = item.trim();
+        if let Some((_, ports)) = PRESETS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(item))
+        {
+            ports.iter().for_each(|port| self.push(*port));
+            return Ok(());
+        }
+
+        let (low, high) = item.split_once('-').unwrap_or((item, item));
+        let parse = |text: &str| {
+            text.trim()
+                .parse::<u16>()
+                .ok()
+                .filter(|port| *port != 0)
+                .ok_or_else(|| {
+                    format!(
+                        "端口无效: {}（可用的预设: {}）",
+                        item,
+                        PRESETS
+                            .iter()
+                            .map(|(name, _)| *name)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )
+                })
+        };
+        let (low, high) = (parse(low)?, parse(high)?);
+        if low > high {
+            return Err(format!("端口范围的起点大于终点: {}", item));
+        }
+        (low..=high).for_each(|port| self.push(port));
+        Ok(())
+    }
+}
+
+impl FromStr for PortSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut set = PortSet::default();
+        for item in s.split(',').filter(|item| !item.trim().is_empty()) {
+            set.add_item(item)?;
+        }
+        if set.ports.is_empty() {
+            return Err("端口集合不能为空".to_string());
+        }
+        Ok(set)
+    }
+}
+
+/* 配置文件中可以写字符串 "80,2052-2096"，也可以写数组 [80, "cf-https"] */
+impl<'de> Deserialize<'de> for PortSet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Item {
+            Port(u16),
+            Text(String),
+        }
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum OneOrMany {
+            One(Item),
+            Many(Vec<Item>),
+        }
+
+        let items = match OneOrMany::deserialize(deserializer)? {
+            OneOrMany::One(item) => vec![item],
+            OneOrMany::Many(items) => items,
+        };
+        let text: Vec<String> = items
+            .into_iter()
+            .map(|item| match item {
+                Item::Port(port) => port.to_string(),
+                Item::Text(text) => text,
+            })
+            .collect();
+        text.join(",").parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ports(text: &str) -> Vec<u16> {
+        text.parse::<PortSet>().unwrap().into_vec()
+    }
+
+    #[test]
+    fn parses_ports_ranges_and_presets() {
+        assert_eq!(ports("80"), [80]);
+        assert_eq!(ports("80, 443"), [80, 443]);
+        assert_eq!(ports("2052-2054"), [2052, 2053, 2054]);
+        assert_eq!(ports("CF-HTTPS"), CF_HTTPS_PORTS);
+        assert_eq!(
+            ports("8443,cf-https,80"),
+            [8443, 443, 2053, 2083, 2087, 2096, 80]
+        );
+    }
+
+    #[test]
+    fn keeps_first_occurrence_order() {
+        assert_eq!(ports("443,80,443,79-81"), [443, 80, 79, 81]);
+    }
+
+    #[test]
+    fn parses_full_range() {
+        let all = ports("1-65535,cf-https,80");
+        assert_eq!(all.len(), 65535);
+        assert_eq!(all[0], 1);
+        assert_eq!(all[65534], 65535);
+    }
+
+    #[test]
+    fn rejects_invalid_items() {
+        for text in ["", ",", "0", "65536", "abc", "90-80", "80-", "cf-ftp"] {
+            assert!(text.parse::<PortSet>().is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn deserializes_string_or_list() {
+        #[derive(Deserialize)]
+        struct Options {
+            ports: PortSet,
+        }
+        let options: Options = toml::from_str(r#"ports = "80,cf-http""#).unwrap();
+        assert_eq!(options.ports.into_vec(), CF_HTTP_PORTS);
+        let options: Options = toml::from_str(r#"ports = [8080, "2052-2053"]"#).unwrap();
+        assert_eq!(options.ports.into_vec(), [8080, 2052, 2053]);
+        let options: Options = toml::from_str("ports = 443").unwrap();
+        assert_eq!(options.ports.into_vec(), [443]);
+    }
+
+    #[test]
+    fn tls_ports() {
+        assert!(is_tls_port(443));
+        assert!(is_tls_port(8443));
+        assert!(!is_tls_port(80));
+    }
+}
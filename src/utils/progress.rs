@@ -0,0 +1,235 @@
+use log::{info, LevelFilter};
+use std::{
+    io::{self, IsTerminal, Write},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+use tokio::{
+    task::JoinHandle,
+    time::{sleep, Duration, Instant},
+};
+
+use crate::utils::{
+    config::Config, data::target_label, error::CustomError, http_request::ProbeResult,
+};
+
+// 实时表格中显示最快的几个结果
+const LIVE_TOP: usize = 5;
+
+// 进度条的宽度(字符)
+const BAR_WIDTH: usize = 30;
+
+// 终端中刷新的间隔，以及输出不是终端（重定向到文件、cron）时打印一行进度的间隔
+const TERMINAL_INTERVAL: Duration = Duration::from_millis(200);
+const PLAIN_INTERVAL: Duration = Duration::from_secs(5);
+
+/* 一次扫描的进度和统计，各个任务并发更新 */
+pub struct Progress {
+    total: usize,
+    concurrency: usize,
+    done: AtomicUsize,
+    success: AtomicUsize,
+    timeout: AtomicUsize,
+    failed: AtomicUsize,
+    in_flight: AtomicUsize,
+    fastest: Mutex<Vec<(f64, String)>>, // (响应时间, 显示的文字)，按响应时间升序
+    start: Instant,
+}
+
+impl Progress {
+    pub fn new(total: usize, config: &Config) -> Arc<Self> {
+        Arc::new(Progress {
+            total,
+            concurrency: config.concurrency,
+            done: AtomicUsize::new(0),
+            success: AtomicUsize::new(0),
+            timeout: AtomicUsize::new(0),
+            failed: AtomicUsize::new(0),
+            in_flight: AtomicUsize::new(0),
+            fastest: Mutex::new(Vec::new()),
+            start: Instant::now(),
+        })
+    }
+
+    /* 拿到信号量、开始请求一个目标 */
+    pub fn begin(&self) {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /* 一个目标请求结束：有HTTP响应的算成功，超时的算超时，其余算失败 */
+    pub fn finish(&self, result: &Result<ProbeResult, CustomError>) {
+        match result {
+            Ok(response) if response.status_code != 0 => {
+                self.success.fetch_add(1, Ordering::Relaxed);
+                let label = format!(
+                    "{:>9.2}ms  {:<24} {:<4} {}",
+                    response.response_time,
+                    target_label(&response.address, response.port),
+                    response.status_code,
+                    response.colo
+                );
+                if let Ok(mut fastest) = self.fastest.lock() {
+                    let index =
+                        fastest.partition_point(|(latency, _)| *latency <= response.response_time);
+                    if index < LIVE_TOP {
+                        fastest.insert(index, (response.response_time, label));
+                        fastest.truncate(LIVE_TOP);
+                    }
+                }
+            }
+            Err(CustomError::Timeout) => {
+                self.timeout.fetch_add(1, Ordering::Relaxed);
+            }
+            _ => {
+                self.failed.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.in_flight.fetch_sub(1, Ordering::Relaxed);
+        self.done.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /* 进度行：进度条、完成数、速率、剩余时间、成功/超时/失败数、当前并发 */
+    fn summary(&self, with_bar: bool) -> String {
+        let done = self.done.load(Ordering::Relaxed);
+        let ratio = if self.total == 0 {
+            1.0
+        } else {
+            done as f64 / self.total as f64
+        };
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let rate = if elapsed > 0.0 {
+            done as f64 / elapsed
+        } else {
+            0.0
+        };
+        let eta = if rate > 0.0 {
+            format_duration((self.total.saturating_sub(done)) as f64 / rate)
+        } else {
+            "--:--:--".to_string()
+        };
+
+        let bar = if with_bar {
+            let filled = ((ratio * BAR_WIDTH as f64) as usize).min(BAR_WIDTH);
+            format!(
+                "[{}{}] ",
+                "#".repeat(filled),
+                "-".repeat(BAR_WIDTH - filled)
+            )
+        } else {
+            String::new()
+        };
+        format!(
+            "{}{}/{} {:.1}% | {:.1}/s | 剩余 {} | 成功 {} 超时 {} 失败 {} | 并发 {}/{}",
+            bar,
+            done,
+            self.total,
+            ratio * 100.0,
+            rate,
+            eta,
+            self.success.load(Ordering::Relaxed),
+            self.timeout.load(Ordering::Relaxed),
+            self.failed.load(Ordering::Relaxed),
+            self.in_flight.load(Ordering::Relaxed),
+            self.concurrency
+        )
+    }
+
+    /* 终端中显示的全部行：进度行和目前最快的几个结果 */
+    fn lines(&self) -> Vec<String> {
+        let mut lines = vec![self.summary(true)];
+        if let Ok(fastest) = self.fastest.lock() {
+            if !fastest.is_empty() {
+                lines.push("目前最快的响应：".to_string());
+                lines.extend(
+                    fastest
+                        .iter()
+                        .enumerate()
+                        .map(|(index, (_, label))| format!("{:>3}. {}", index + 1, label)),
+                );
+            }
+        }
+        lines
+    }
+}
+
+/* 秒数显示为 时:分:秒 */
+fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+/* 显示进度的后台任务：输出是终端时原地刷新进度条和实时表格，否则定时打印一行进度 */
+pub struct Reporter {
+    progress: Arc<Progress>,
+    task: Option<JoinHandle<()>>,
+    terminal: bool,
+    drawn: Arc<AtomicUsize>, // 终端中上一次画了几行，重画时先回到第一行
+}
+
+impl Reporter {
+    /* 没有开启进度显示，或者日志级别为debug（逐行输出每个请求）时，不显示进度 */
+    pub fn start(progress: Arc<Progress>, config: &Config) -> Self {
+        let terminal = io::stderr().is_terminal();
+        let drawn = Arc::new(AtomicUsize::new(0));
+        let task = (config.progress && config.log_level < LevelFilter::Debug).then(|| {
+            let progress = Arc::clone(&progress);
+            let drawn = Arc::clone(&drawn);
+            tokio::spawn(async move {
+                loop {
+                    if terminal {
+                        sleep(TERMINAL_INTERVAL).await;
+                        let lines = redraw(&progress.lines(), drawn.load(Ordering::Relaxed));
+                        drawn.store(lines, Ordering::Relaxed);
+                    } else {
+                        sleep(PLAIN_INTERVAL).await;
+                        info!("进度：{}", progress.summary(false));
+                    }
+                }
+            })
+        });
+        Reporter {
+            progress,
+            task,
+            terminal,
+            drawn,
+        }
+    }
+
+    /* 扫描结束：停止刷新，最后显示一次完整的进度 */
+    pub async fn stop(self) {
+        let Some(task) = self.task else {
+            return;
+        };
+        task.abort();
+        let _ = task.await;
+        if self.terminal {
+            redraw(&self.progress.lines(), self.drawn.load(Ordering::Relaxed));
+        } else {
+            info!("进度：{}", self.progress.summary(false));
+        }
+    }
+}
+
+/* 把光标移回上一次画的第一行，清掉之后的内容再画新的几行，返回画了几行 */
+fn redraw(lines: &[String], drawn: usize) -> usize {
+    let mut stderr = io::stderr().lock();
+    let mut output = String::new();
+    if drawn > 0 {
+        output.push_str(&format!("\x1b[{}F", drawn));
+    }
+    output.push_str("\x1b[J");
+    for line in lines {
+        output.push_str(line);
+        output.push('\n');
+    }
+    let _ = stderr.write_all(output.as_bytes());
+    let _ = stderr.flush();
+    lines.len()
+}
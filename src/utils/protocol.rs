@@ -0,0 +1,110 @@
+use std::{process::Stdio, str};
+use tokio::time::timeout;
+
+use crate::utils::{common::NULL_DEVICE, config::Config};
+
+/* 目标支持的HTTP协议 */
+#[derive(Debug, Clone, Default)]
+pub struct ProtocolInfo {
+    pub http_version: String, // 实际使用的HTTP版本：1.0、1.1、2
+    pub h2: Option<bool>,     // https目标通过ALPN协商到HTTP/2，http目标为None
+    pub alt_svc: Vec<String>, // alt-svc响应头宣告的协议，例如 h3、h3-29
+    pub h3: Option<bool>,     // HTTP/3（QUIC）握手是否成功，没有尝试时为None
+}
+
+/* 状态行中的HTTP版本，例如 "HTTP/2 301" -> "2"，"HTTP/1.1 200 OK" -> "1.1" */
+pub fn status_line_version(headers: &str) -> String {
+    headers
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().next())
+        .and_then(|version| version.strip_prefix("HTTP/"))
+        .unwrap_or("")
+        .to_string()
+}
+
+/* 解析alt-svc的值，例如 'h3=":443"; ma=86400, h3-29=":443"' -> ["h3", "h3-29"]，"clear"表示没有 */
+pub fn parse_alt_svc(value: &str) -> Vec<String> {
+    let mut protocols: Vec<String> = Vec::new();
+    for entry in value.split(',') {
+        let protocol = entry
+            .split(';')
+            .next()
+            .and_then(|alternative| alternative.split_once('='))
+            .map(|(protocol, _)| protocol.trim().to_string())
+            .unwrap_or_default();
+        if !protocol.is_empty() && !protocols.contains(&protocol) {
+            protocols.push(protocol);
+        }
+    }
+    protocols
+}
+
+/* 只用HTTP/3请求一次，能拿到HTTP/3的响应就说明QUIC握手成功 */
+pub async fn probe_http3(url: &str, extra_args: &[String], config: &Config) -> bool {
+    let output = timeout(
+        config.timeout,
+        tokio::process::Command::new("curl")
+            .arg("-s")
+            .arg("-I")
+            .arg("--http3-only")
+            .args(extra_args)
+            .arg("-o")
+            .arg(NULL_DEVICE)
+            .arg("-w")
+            .arg("%{http_version}")
+            .arg(url)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await;
+
+    match output {
+        Ok(Ok(output)) => str::from_utf8(&output.stdout).unwrap_or("").trim() == "3",
+        _ => false,
+    }
+}
+
+/* 检查curl是否支持HTTP/3（curl -V 的Features中有HTTP3） */
+pub async fn is_http3_supported() -> bool {
+    let output = tokio::process::Command::new("curl")
+        .arg("-V")
+        .output()
+        .await;
+
+    match output {
+        Ok(output) => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|line| line.starts_with("Features:"))
+            .any(|line| line.split_whitespace().any(|feature| feature == "HTTP3")),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_version_from_status_line() {
+        assert_eq!(status_line_version("HTTP/2 301\r\nserver: x\r\n"), "2");
+        assert_eq!(status_line_version("HTTP/1.1 200 OK\r\n"), "1.1");
+        assert_eq!(status_line_version("HTTP/1.0 404 Not Found"), "1.0");
+        assert_eq!(status_line_version(""), "");
+        assert_eq!(status_line_version("garbage"), "");
+    }
+
+    #[test]
+    fn parses_alt_svc_protocols() {
+        assert_eq!(
+            parse_alt_svc(r#"h3=":443"; ma=86400, h3-29=":443"; ma=86400"#),
+            ["h3", "h3-29"]
+        );
+        assert_eq!(parse_alt_svc(r#"h3=":443", h3=":8443""#), ["h3"]);
+        assert_eq!(parse_alt_svc(r#" h2="alt.example.com:443" "#), ["h2"]);
+        assert!(parse_alt_svc("clear").is_empty());
+        assert!(parse_alt_svc("").is_empty());
+    }
+}
@@ -0,0 +1,177 @@
+use url::Url;
+
+/* 跳转链中的一跳 */
+#[derive(Debug, Clone)]
+pub struct RedirectHop {
+    pub url: String,
+    pub status_code: u16,
+    pub server: String,
+}
+
+/* 跟随跳转时，curl会依次输出每个响应的响应头；按状态行拆分成多段，
+去掉100 Continue、103 Early Hints等1xx临时响应 */
+pub fn split_header_blocks(headers: &str) -> Vec<String> {
+    let mut blocks: Vec<String> = Vec::new();
+    for line in headers.lines() {
+        if line.starts_with("HTTP/") || blocks.is_empty() {
+            blocks.push(String::new());
+        }
+        if let Some(block) = blocks.last_mut() {
+            block.push_str(line);
+            block.push('\n');
+        }
+    }
+    blocks.retain(|block| !block.trim().is_empty() && !(100..200).contains(&status_code(block)));
+    blocks
+}
+
+/* 一段响应头的状态码，无法解析时为0 */
+fn status_code(block: &str) -> u16 {
+    block
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .unwrap_or(0)
+}
+
+/* 一段响应头中某个响应头的第一个值（忽略大小写） */
+fn header_value(block: &str, name: &str) -> String {
+    block
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim().to_string())
+        .unwrap_or_default()
+}
+
+/* 由每段响应头还原跳转链：第一跳是请求的地址，之后每一跳是上一跳的Location（相对地址按上一跳解析）；
+遇到不是3xx或者没有Location的响应就结束 */
+pub fn build_chain(url: &str, blocks: &[String]) -> Vec<RedirectHop> {
+    let mut chain = Vec::with_capacity(blocks.len());
+    let mut current = Url::parse(url)
+        .map(|url| url.to_string())
+        .unwrap_or_else(|_| url.to_string());
+    for block in blocks {
+        let status_code = status_code(block);
+        chain.push(RedirectHop {
+            url: current.clone(),
+            status_code,
+            server: header_value(block, "server"),
+        });
+
+        let location = header_value(block, "location");
+        if location.is_empty() || !(300..400).contains(&status_code) {
+            break;
+        }
+        current = match Url::parse(&current).and_then(|base| base.join(&location)) {
+            Ok(next) => next.to_string(),
+            Err(_) => location,
+        };
+    }
+    chain
+}
+
+/* 跳转链的文字形式，例如 "301 http://a/ (nginx) -> 200 https://b/ (cloudflare)" */
+pub fn format_chain(chain: &[RedirectHop]) -> String {
+    chain
+        .iter()
+        .map(|hop| {
+            if hop.server.is_empty() {
+                format!("{} {}", hop.status_code, hop.url)
+            } else {
+                format!("{} {} ({})", hop.status_code, hop.url, hop.server)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain(url: &str, headers: &str) -> Vec<(u16, String, String)> {
+        build_chain(url, &split_header_blocks(headers))
+            .into_iter()
+            .map(|hop| (hop.status_code, hop.url, hop.server))
+            .collect()
+    }
+
+    fn hop(status_code: u16, url: &str, server: &str) -> (u16, String, String) {
+        (status_code, url.to_string(), server.to_string())
+    }
+
+    #[test]
+    fn splits_blocks_and_drops_interim_responses() {
+        let headers = "HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 301 Moved\r\nLocation: /a\r\n\r\n\
+                       HTTP/2 103\r\nlink: </x.css>\r\n\r\nHTTP/2 200\r\nserver: cf\r\n\r\n";
+        let blocks = split_header_blocks(headers);
+        assert_eq!(blocks.len(), 2);
+        assert!(blocks[0].starts_with("HTTP/1.1 301"));
+        assert!(blocks[1].starts_with("HTTP/2 200"));
+        assert!(split_header_blocks("").is_empty());
+    }
+
+    #[test]
+    fn resolves_relative_locations() {
+        let headers = "HTTP/1.1 301 Moved\r\nServer: nginx\r\nLocation: /login?next=1\r\n\r\n\
+                       HTTP/1.1 302 Found\r\nLocation: ../home\r\n\r\n\
+                       HTTP/1.1 200 OK\r\nServer: cloudflare\r\n\r\n";
+        assert_eq!(
+            chain("http://example.com/a/b", headers),
+            [
+                hop(301, "http://example.com/a/b", "nginx"),
+                hop(302, "http://example.com/login?next=1", ""),
+                hop(200, "http://example.com/home", "cloudflare"),
+            ]
+        );
+    }
+
+    #[test]
+    fn follows_absolute_locations() {
+        let headers =
+            "HTTP/1.1 308 Permanent Redirect\r\nlocation: https://www.example.com:8443/x\r\n\r\n\
+                       HTTP/2 200\r\nserver: nginx\r\n\r\n";
+        assert_eq!(
+            chain("http://1.1.1.1:80", headers),
+            [
+                hop(308, "http://1.1.1.1/", ""),
+                hop(200, "https://www.example.com:8443/x", "nginx"),
+            ]
+        );
+    }
+
+    #[test]
+    fn ends_on_non_redirect_responses() {
+        // 没有Location的3xx、带Location的非3xx都结束跳转链
+        let headers = "HTTP/1.1 302 Found\r\nLocation: /next\r\n\r\n\
+                       HTTP/1.1 201 Created\r\nLocation: /item/1\r\n\r\n\
+                       HTTP/1.1 200 OK\r\n\r\n";
+        assert_eq!(
+            chain("http://example.com/", headers),
+            [
+                hop(302, "http://example.com/", ""),
+                hop(201, "http://example.com/next", ""),
+            ]
+        );
+
+        let headers = "HTTP/1.1 304 Not Modified\r\nServer: nginx\r\n\r\n";
+        assert_eq!(
+            chain("http://example.com/", headers),
+            [hop(304, "http://example.com/", "nginx")]
+        );
+    }
+
+    #[test]
+    fn formats_chain() {
+        let chain = build_chain(
+            "http://a.com/",
+            &split_header_blocks("HTTP/1.1 301 Moved\nServer: nginx\nLocation: https://b.com/\n\nHTTP/1.1 200 OK\n\n"),
+        );
+        assert_eq!(
+            format_chain(&chain),
+            "301 http://a.com/ (nginx) -> 200 https://b.com/"
+        );
+    }
+}
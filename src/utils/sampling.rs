@@ -0,0 +1,159 @@
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use std::{
+    collections::{HashMap, HashSet},
+    net::Ipv4Addr,
+};
+
+use crate::utils::{config::Config, data::Target, http_request::ProbeResult};
+
+/* IPv4地址所在的/24网段，域名返回None */
+pub fn subnet_24(host: &str) -> Option<[u8; 3]> {
+    let [a, b, c, _] = host.parse::<Ipv4Addr>().ok()?.octets();
+    Some([a, b, c])
+}
+
+/* 按/24网段抽样：返回(抽中的目标, 没抽中的目标)，域名总是被抽中 */
+pub fn sample_targets(
+    targets: Vec<Target>,
+    config: &Config,
+    seed: u64,
+) -> (Vec<Target>, Vec<Target>) {
+    if !config.is_sampling() {
+        return (targets, Vec::new());
+    }
+
+    let mut sampled: Vec<Target> = Vec::new();
+    let mut group_index: HashMap<[u8; 3], usize> = HashMap::new();
+    let mut groups: Vec<Vec<Target>> = Vec::new();
+    for target in targets {
+        match subnet_24(&target.host) {
+            Some(subnet) => {
+                let index = *group_index.entry(subnet).or_insert_with(|| {
+                    groups.push(Vec::new());
+                    groups.len() - 1
+                });
+                groups[index].push(target);
+            }
+            None => sampled.push(target),
+        }
+    }
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut rest: Vec<Target> = Vec::new();
+    for mut group in groups {
+        // 每个网段至少抽一个，有响应时才能据此扩展
+        let count = match (config.sample_per_24, config.sample_percent) {
+            (Some(count), _) => count,
+            (None, Some(percent)) => (group.len() as f64 * percent / 100.0).ceil() as usize,
+            (None, None) => group.len(),
+        }
+        .clamp(1, group.len());
+        group.shuffle(&mut rng);
+        rest.extend(group.split_off(count));
+        sampled.extend(group);
+    }
+
+    (sampled, rest)
+}
+
+/* 从没抽中的目标中，挑出与有响应的结果位于同一/24网段的目标 */
+pub fn responsive_remainder(rest: Vec<Target>, results: &[ProbeResult]) -> Vec<Target> {
+    let responsive: HashSet<[u8; 3]> = results
+        .iter()
+        .filter_map(|result| subnet_24(&result.address))
+        .collect();
+    rest.into_iter()
+        .filter(|target| subnet_24(&target.host).is_some_and(|subnet| responsive.contains(&subnet)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 10.0.1.0/24 中10个地址、10.0.2.0/24 中3个地址，再加一个域名
+    fn input() -> Vec<Target> {
+        let mut hosts: Vec<String> = (1..=10).map(|last| format!("10.0.1.{}", last)).collect();
+        hosts.extend((1..=3).map(|last| format!("10.0.2.{}", last)));
+        hosts.push("example.com".to_string());
+        hosts.into_iter().map(Target::new).collect()
+    }
+
+    fn sampling(per_24: Option<usize>, percent: Option<f64>) -> Config {
+        Config {
+            sample_per_24: per_24,
+            sample_percent: percent,
+            ..Config::default()
+        }
+    }
+
+    fn count_in(targets: &[Target], subnet: [u8; 3]) -> usize {
+        targets
+            .iter()
+            .filter(|target| subnet_24(&target.host) == Some(subnet))
+            .count()
+    }
+
+    fn hosts(targets: &[Target]) -> Vec<&str> {
+        targets.iter().map(|target| target.host.as_str()).collect()
+    }
+
+    #[test]
+    fn keeps_everything_without_sampling() {
+        let (sampled, rest) = sample_targets(input(), &Config::default(), 1);
+        assert_eq!(sampled, input());
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn same_seed_gives_same_sample() {
+        let config = sampling(Some(2), None);
+        let (first, first_rest) = sample_targets(input(), &config, 42);
+        let (second, second_rest) = sample_targets(input(), &config, 42);
+        assert_eq!(first, second);
+        assert_eq!(first_rest, second_rest);
+        assert_eq!(first.len() + first_rest.len(), input().len());
+    }
+
+    #[test]
+    fn samples_per_24_and_keeps_domains() {
+        let (sampled, rest) = sample_targets(input(), &sampling(Some(2), None), 7);
+        assert_eq!(count_in(&sampled, [10, 0, 1]), 2);
+        assert_eq!(count_in(&sampled, [10, 0, 2]), 2);
+        assert!(hosts(&sampled).contains(&"example.com"));
+        assert_eq!(count_in(&rest, [10, 0, 1]), 8);
+        assert_eq!(count_in(&rest, [10, 0, 2]), 1);
+        assert!(!hosts(&rest).contains(&"example.com"));
+
+        // K大于网段中的地址数时，整个网段都被抽中
+        let (sampled, rest) = sample_targets(input(), &sampling(Some(50), None), 7);
+        assert_eq!(sampled.len(), input().len());
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn rounds_percent_up_with_at_least_one_per_24() {
+        // 25%：10个地址向上取整为3个，3个地址向上取整为1个
+        let (sampled, _) = sample_targets(input(), &sampling(None, Some(25.0)), 3);
+        assert_eq!(count_in(&sampled, [10, 0, 1]), 3);
+        assert_eq!(count_in(&sampled, [10, 0, 2]), 1);
+
+        // 很小的百分比，每个网段也至少抽一个
+        let (sampled, _) = sample_targets(input(), &sampling(None, Some(0.1)), 3);
+        assert_eq!(count_in(&sampled, [10, 0, 1]), 1);
+        assert_eq!(count_in(&sampled, [10, 0, 2]), 1);
+        assert!(hosts(&sampled).contains(&"example.com"));
+    }
+
+    #[test]
+    fn expands_only_responsive_subnets() {
+        let (_, rest) = sample_targets(input(), &sampling(Some(1), None), 9);
+        let results = [
+            ProbeResult::new_for_test("10.0.2.200", 80),
+            ProbeResult::new_for_test("example.com", 80),
+        ];
+        let remainder = responsive_remainder(rest, &results);
+        assert_eq!(remainder.len(), 2);
+        assert_eq!(count_in(&remainder, [10, 0, 2]), 2);
+    }
+}
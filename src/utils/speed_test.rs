@@ -0,0 +1,232 @@
+use log::{info, warn};
+use std::{process::Stdio, str};
+use tokio::{
+    io::AsyncReadExt,
+    time::{timeout, timeout_at, Duration, Instant},
+};
+use url::Url;
+
+use crate::utils::{
+    common::{get_current_time, NULL_DEVICE},
+    config::Config,
+    error::CustomError,
+    http_request::ProbeResult,
+    proxy::{proxy_args, Proxy},
+};
+
+/* 从扫描结果中挑选响应时间最短的前N个地址，依次测速并记录下载速度，返回测速失败的数量 */
+pub async fn run_speed_tests(results: &mut [ProbeResult], config: &Config) -> usize {
+    if config.speed_test_top == 0 {
+        return 0;
+    }
+
+    let mut candidates: Vec<&mut ProbeResult> = results
+        .iter_mut()
+        .filter(|result| result.status_code != 0)
+        .collect();
+    candidates.sort_by(|a, b| a.response_time.total_cmp(&b.response_time));
+
+    // 测速会占满带宽，所以不并发，一个一个地测
+    let mut failed: usize = 0;
+    for result in candidates.into_iter().take(config.speed_test_top) {
+        let download = download_speed(
+            &result.address,
+            result.proxy.as_ref(),
+            result.source.as_deref(),
+            config,
+        );
+        match download.await {
+            Ok((speed, _)) => {
+                info!(
+                    "{} {} -> Speed test finished, {:.2} MB/s",
+                    get_current_time(),
+                    result.address,
+                    speed
+                );
+                result.download_speed = Some(speed);
+            }
+            Err(err) => {
+                warn!(
+                    "{} {} -> Speed test failed: {}",
+                    get_current_time(),
+                    result.address,
+                    err
+                );
+                failed += 1;
+            }
+        }
+    }
+    failed
+}
+
+/* 从被测地址（以及扫描时使用的代理和源地址）下载测速文件，返回(下载速度(MB/s), 下载的字节数)；
+连接的是测速地址中的端口，不是扫描时的端口 */
+async fn download_speed(
+    address: &str,
+    proxy: Option<&Proxy>,
+    source: Option<&str>,
+    config: &Config,
+) -> Result<(f64, u64), CustomError> {
+    let url = Url::parse(&config.speed_test_url)
+        .map_err(|err| CustomError::ConfigError(format!("测速地址无效: {}", err)))?;
+    let host = url.host_str().unwrap_or("");
+    let port = url.port_or_known_default().unwrap_or(80);
+
+    // 保留测速地址中的域名作为Host和SNI，实际连接到被测地址
+    let mut command = tokio::process::Command::new("curl");
+    command
+        .arg("-s")
+        .arg("--connect-to")
+        .arg(format!("{}:{}:{}:{}", host, port, address, port))
+        .args(proxy_args(proxy, config))
+        .arg("--max-time")
+        .arg(config.speed_test_duration.to_string());
+    if let Some(source) = source {
+        command.arg("--interface").arg(source);
+    }
+    if let Some(bytes) = config.speed_test_bytes {
+        command
+            .arg("-r")
+            .arg(format!("0-{}", bytes.saturating_sub(1)));
+    }
+
+    // 给curl自己的--max-time留一点余量
+    let limit = Duration::from_secs(config.speed_test_duration + 5);
+    match config.speed_test_bytes {
+        Some(bytes) => download_limited(command, url.as_str(), bytes, limit).await,
+        None => download_all(command, url.as_str(), limit).await,
+    }
+}
+
+/* 没有字节数上限：由curl下载并统计速度 */
+async fn download_all(
+    mut command: tokio::process::Command,
+    url: &str,
+    limit: Duration,
+) -> Result<(f64, u64), CustomError> {
+    command
+        .arg("-o")
+        .arg(NULL_DEVICE)
+        .arg("-w")
+        .arg("%{speed_download} %{size_download}")
+        .arg(url)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    let output = match timeout(limit, command.output()).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(err)) => return Err(CustomError::CommandExecutionFailed(err.to_string())),
+        Err(_) => return Err(CustomError::Timeout),
+    };
+
+    // 达到--max-time时curl会返回非0的退出码，但已下载的部分仍然有效，所以只看下载量
+    let stdout = str::from_utf8(&output.stdout).unwrap_or("");
+    let mut values = stdout
+        .split_whitespace()
+        .map(|value| value.parse::<f64>().unwrap_or(0.0));
+    let speed = values.next().unwrap_or(0.0);
+    let size = values.next().unwrap_or(0.0);
+    if size <= 0.0 {
+        return Err(CustomError::CommandExecutionFailed(format!(
+            "没有下载到任何数据，curl退出码: {}",
+            output.status.code().unwrap_or(-1)
+        )));
+    }
+
+    Ok((speed / 1024.0 / 1024.0, size as u64))
+}
+
+/* 有字节数上限：服务器不一定支持Range，所以自己读取curl的输出，达到上限就结束curl，按实际耗时计算速度 */
+async fn download_limited(
+    mut command: tokio::process::Command,
+    url: &str,
+    bytes: u64,
+    limit: Duration,
+) -> Result<(f64, u64), CustomError> {
+    let start_time = Instant::now();
+    let mut child = command
+        .arg(url)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|err| CustomError::CommandExecutionFailed(err.to_string()))?;
+    let Some(mut stdout) = child.stdout.take() else {
+        return Err(CustomError::CommandExecutionFailed(
+            "无法读取curl的输出".to_string(),
+        ));
+    };
+
+    let deadline = start_time + limit;
+    let mut size: u64 = 0;
+    let mut chunk = [0u8; 65536];
+    while size < bytes {
+        match timeout_at(deadline, stdout.read(&mut chunk)).await {
+            Ok(Ok(0)) | Ok(Err(_)) | Err(_) => break,
+            Ok(Ok(read)) => size += read as u64,
+        }
+    }
+    let elapsed = start_time.elapsed().as_secs_f64();
+    let _ = child.kill().await;
+
+    let size = size.min(bytes);
+    if size == 0 {
+        return Err(CustomError::CommandExecutionFailed(
+            "没有下载到任何数据".to_string(),
+        ));
+    }
+    Ok((size as f64 / elapsed / 1024.0 / 1024.0, size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{io::AsyncWriteExt, net::TcpListener};
+
+    /* 本地的测速服务器：不理会Range，总是返回body_size字节的正文 */
+    async fn serve(body_size: usize) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 4096];
+            let _ = stream.read(&mut request).await;
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body_size
+            );
+            let _ = stream.write_all(header.as_bytes()).await;
+            let _ = stream.write_all(&vec![b'0'; body_size]).await;
+        });
+        port
+    }
+
+    fn config(port: u16, bytes: Option<u64>) -> Config {
+        Config {
+            speed_test_url: format!("http://speed.test:{}/down", port),
+            speed_test_bytes: bytes,
+            ..Config::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn downloads_whole_file_without_limit() {
+        let port = serve(200_000).await;
+        let (speed, size) = download_speed("127.0.0.1", None, None, &config(port, None))
+            .await
+            .unwrap();
+        assert_eq!(size, 200_000);
+        assert!(speed > 0.0);
+    }
+
+    #[tokio::test]
+    async fn stops_at_byte_limit_when_range_is_ignored() {
+        let port = serve(20_000_000).await;
+        let (speed, size) = download_speed("127.0.0.1", None, None, &config(port, Some(100_000)))
+            .await
+            .unwrap();
+        assert_eq!(size, 100_000);
+        assert!(speed > 0.0);
+    }
+}
@@ -0,0 +1,194 @@
+use csv::WriterBuilder;
+use futures::stream::{self, StreamExt};
+use log::info;
+use std::{
+    fs::OpenOptions,
+    io::{self, ErrorKind},
+    net::{IpAddr, SocketAddr},
+    path::Path,
+};
+use tokio::{
+    net::{lookup_host, TcpSocket, TcpStream},
+    time::{timeout, Instant},
+};
+
+use crate::utils::{
+    config::Config,
+    data::{target_ports, Target},
+    error::CustomError,
+};
+
+/* TCP连接的结果 */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortState {
+    Open,     // 连接成功
+    Closed,   // 连接被拒绝
+    Filtered, // 超时或不可达
+}
+
+impl PortState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PortState::Open => "open",
+            PortState::Closed => "closed",
+            PortState::Filtered => "filtered",
+        }
+    }
+}
+
+/* 尝试建立一次TCP连接（指定了源地址时从该地址发出），返回端口状态和耗时(毫秒) */
+async fn connect(
+    host: &str,
+    port: u16,
+    source: Option<IpAddr>,
+    config: &Config,
+) -> (PortState, f64) {
+    let start = Instant::now();
+    let stream = async {
+        match source {
+            Some(source) => connect_from(host, port, source).await,
+            None => TcpStream::connect((host, port)).await,
+        }
+    };
+    let state = match timeout(config.tcp_timeout, stream).await {
+        Ok(Ok(_)) => PortState::Open,
+        Ok(Err(err)) if err.kind() == ErrorKind::ConnectionRefused => PortState::Closed,
+        _ => PortState::Filtered,
+    };
+    (state, start.elapsed().as_secs_f64() * 1000.0)
+}
+
+/* 绑定源地址后再连接，目标是域名时取与源地址同类型的IP */
+async fn connect_from(host: &str, port: u16, source: IpAddr) -> io::Result<TcpStream> {
+    let address = lookup_host((host, port))
+        .await?
+        .find(|address| address.is_ipv4() == source.is_ipv4())
+        .ok_or_else(|| io::Error::new(ErrorKind::AddrNotAvailable, "没有与源地址同类型的IP"))?;
+    let socket = if address.is_ipv4() {
+        TcpSocket::new_v4()?
+    } else {
+        TcpSocket::new_v6()?
+    };
+    socket.bind(SocketAddr::new(source, 0))?;
+    socket.connect(address).await
+}
+
+/* TCP预检：并发连接每个目标的每个端口，只把端口开放的目标交给HTTP阶段；每个返回的目标都指定了端口。
+指定了多个源地址时从每个源地址各连接一次，任意一个源地址能连上就算开放 */
+pub async fn tcp_precheck(targets: &[Target], config: &Config) -> Result<Vec<Target>, CustomError> {
+    let jobs: Vec<Target> = targets
+        .iter()
+        .flat_map(|target| {
+            target_ports(target, config).into_iter().map(|port| Target {
+                port: Some(port),
+                ..target.clone()
+            })
+        })
+        .collect();
+
+    // 配置检查时已经确认预检的源地址都是IP
+    let sources: Vec<Option<IpAddr>> = if config.sources.is_empty() {
+        vec![None]
+    } else {
+        config
+            .sources
+            .iter()
+            .filter_map(|source| source.parse().ok())
+            .map(Some)
+            .collect()
+    };
+
+    let checks: Vec<(usize, Option<IpAddr>)> = sources
+        .iter()
+        .flat_map(|source| (0..jobs.len()).map(move |index| (index, *source)))
+        .collect();
+    let checked: Vec<(usize, Option<IpAddr>, PortState, f64)> = stream::iter(checks)
+        .map(|(index, source)| {
+            let target = &jobs[index];
+            async move {
+                let (state, elapsed) = connect(
+                    &target.host,
+                    target.port.unwrap_or_default(),
+                    source,
+                    config,
+                )
+                .await;
+                (index, source, state, elapsed)
+            }
+        })
+        .buffer_unordered(config.tcp_concurrency)
+        .collect()
+        .await;
+
+    let count = |wanted: PortState| {
+        checked
+            .iter()
+            .filter(|(_, _, state, _)| *state == wanted)
+            .count()
+    };
+    info!(
+        "TCP预检：{}个端口开放，{}个关闭，{}个无响应",
+        count(PortState::Open),
+        count(PortState::Closed),
+        count(PortState::Filtered)
+    );
+
+    if let Some(file_path) = &config.tcp_output {
+        write_tcp_results(file_path, &jobs, &checked, !config.sources.is_empty())?;
+    }
+
+    let mut open = vec![false; jobs.len()];
+    for (index, _, state, _) in &checked {
+        if *state == PortState::Open {
+            open[*index] = true;
+        }
+    }
+    Ok(jobs
+        .into_iter()
+        .zip(open)
+        .filter(|(_, open)| *open)
+        .map(|(target, _)| target)
+        .collect())
+}
+
+/* 追加写入TCP预检的结果，文件不存在时先写标题；指定了源地址时增加源地址列 */
+fn write_tcp_results(
+    file_path: &str,
+    jobs: &[Target],
+    checked: &[(usize, Option<IpAddr>, PortState, f64)],
+    with_source: bool,
+) -> Result<(), CustomError> {
+    let exists = Path::new(file_path).exists();
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(file_path)
+        .map_err(|err| CustomError::output(file_path, err))?;
+    let mut writer = WriterBuilder::new().from_writer(file);
+    let to_error = |err: csv::Error| CustomError::output(file_path, err);
+
+    if !exists {
+        let mut header = vec!["网络地址", "端口", "状态", "耗时(ms)"];
+        if with_source {
+            header.push("源地址");
+        }
+        writer.write_record(header).map_err(to_error)?;
+    }
+    for (index, source, state, elapsed) in checked {
+        let target = &jobs[*index];
+        let mut record = vec![
+            target.host.clone(),
+            target.port.unwrap_or_default().to_string(),
+            state.as_str().to_string(),
+            format!("{:.0}", elapsed),
+        ];
+        if with_source {
+            record.push(source.map(|source| source.to_string()).unwrap_or_default());
+        }
+        writer.write_record(record).map_err(to_error)?;
+    }
+    writer
+        .flush()
+        .map_err(|err| CustomError::output(file_path, err))?;
+    Ok(())
+}
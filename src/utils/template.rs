@@ -0,0 +1,328 @@
+use serde::{Deserialize, Deserializer};
+use std::str::FromStr;
+
+use crate::utils::{
+    benchmark::LatencyStats, data::target_label, http_request::ProbeResult, redirect::format_chain,
+};
+
+// 模板中可以使用的字段
+const FIELDS: &[&str] = &[
+    "address",
+    "ip",
+    "port",
+    "path",
+    "tags",
+    "target",
+    "url",
+    "latency",
+    "status",
+    "colo",
+    "country",
+    "server",
+    "speed",
+    "min",
+    "median",
+    "p90",
+    "max",
+    "jitter",
+    "loss",
+    "dns",
+    "connect",
+    "tls",
+    "ttfb",
+    "title",
+    "length",
+    "sha256",
+    "mmh3",
+    "fingerprint",
+    "final_url",
+    "final_status",
+    "redirects",
+    "trace_ip",
+    "trace_loc",
+    "trace_tls",
+    "trace_http",
+    "trace_warp",
+    "trace_sni",
+    "license_version",
+    "http_version",
+    "h2",
+    "alt_svc",
+    "h3",
+    "proxy",
+    "source",
+];
+
+/* 行模板中的一段 */
+#[derive(Debug, Clone)]
+enum Part {
+    Literal(String),
+    Field(String),
+}
+
+/* 地址列表文件的行模板，例如 "{ip}:{port}#{colo}-{latency}ms"，"{{"和"}}"表示花括号本身 */
+#[derive(Debug, Clone)]
+pub struct LineTemplate {
+    parts: Vec<Part>,
+}
+
+impl FromStr for LineTemplate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(format!("模板中的 {{{} 缺少 }}", name)),
+                        }
+                    }
+                    let name = name.trim().to_lowercase();
+                    let is_header = name
+                        .strip_prefix("header:")
+                        .is_some_and(|header| !header.trim().is_empty());
+                    if !is_header && !FIELDS.contains(&name.as_str()) {
+                        return Err(format!(
+                            "未知的模板字段 {{{}}}，可用的字段: {}, header:响应头名称",
+                            name,
+                            FIELDS.join(", ")
+                        ));
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Field(name));
+                }
+                '}' => return Err("模板中有多余的 }，花括号本身请写成 }}".to_string()),
+                _ => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+
+        Ok(LineTemplate { parts })
+    }
+}
+
+impl<'de> Deserialize<'de> for LineTemplate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl LineTemplate {
+    /* 模板是否以 {url} 开头，并且之后是行尾、"#"或空白，这样的行可以直接用开头的URL去重和重新验证 */
+    pub fn starts_with_url(&self) -> bool {
+        let rest = match self.parts.as_slice() {
+            [Part::Field(name), rest @ ..] if name == "url" => rest,
+            _ => return false,
+        };
+        match rest.first() {
+            None => true,
+            Some(Part::Literal(text)) => text.starts_with(|c: char| c == '#' || c.is_whitespace()),
+            Some(Part::Field(_)) => false,
+        }
+    }
+
+    /* 用一条探测结果填充模板，没有值的字段替换为空字符串 */
+    pub fn render(&self, result: &ProbeResult) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Literal(text) => text.clone(),
+                Part::Field(name) => field_value(result, name),
+            })
+            .collect()
+    }
+}
+
+/* 获取结果中某个字段的值 */
+fn field_value(result: &ProbeResult, name: &str) -> String {
+    let stats = result.latency_stats;
+    let stat = |value: fn(&LatencyStats) -> f64| {
+        stats
+            .as_ref()
+            .map(|stats| format!("{:.0}", value(stats)))
+            .unwrap_or_default()
+    };
+
+    match name {
+        "address" | "ip" => result.address.clone(),
+        "port" => result.port.to_string(),
+        "path" => result.path.clone(),
+        "tags" => result.tags.join(","),
+        "target" => target_label(&result.address, result.port),
+        "url" => result.base_url(),
+        "latency" => format!("{:.0}", result.response_time),
+        "status" => result.status_code.to_string(),
+        "colo" => result.colo.clone(),
+        "country" => result.country_code.clone(),
+        "server" => result.server.clone(),
+        "speed" => result
+            .download_speed
+            .map(|speed| format!("{:.2}", speed))
+            .unwrap_or_default(),
+        "min" => stat(|s| s.min),
+        "median" => stat(|s| s.median),
+        "p90" => stat(|s| s.p90),
+        "max" => stat(|s| s.max),
+        "jitter" => stat(|s| s.jitter),
+        "loss" => stat(|s| s.loss),
+        "dns" => stat(|s| s.dns),
+        "connect" => stat(|s| s.connect),
+        "tls" => stat(|s| s.tls),
+        "ttfb" => stat(|s| s.ttfb),
+        "final_url" => result.final_url.clone(),
+        "final_status" => result.final_status.to_string(),
+        "redirects" => format_chain(&result.redirects),
+        "trace_ip" | "trace_loc" | "trace_tls" | "trace_http" | "trace_warp" | "trace_sni" => {
+            match &result.cf_trace {
+                Some(trace) => match name {
+                    "trace_ip" => trace.ip.clone(),
+                    "trace_loc" => trace.loc.clone(),
+                    "trace_tls" => trace.tls.clone(),
+                    "trace_http" => trace.http.clone(),
+                    "trace_warp" => trace.warp.clone(),
+                    _ => trace.sni.clone(),
+                },
+                None => String::new(),
+            }
+        }
+        "license_version" => result
+            .license_server
+            .as_ref()
+            .map(|info| info.version.clone())
+            .unwrap_or_default(),
+        "source" => result.source.clone().unwrap_or_default(),
+        "proxy" => result
+            .proxy
+            .as_ref()
+            .map(|proxy| proxy.to_string())
+            .unwrap_or_default(),
+        "http_version" | "h2" | "alt_svc" | "h3" => match &result.protocol {
+            Some(protocol) => match name {
+                "http_version" => protocol.http_version.clone(),
+                "alt_svc" => protocol.alt_svc.join(","),
+                _ => {
+                    let support = if name == "h2" {
+                        protocol.h2
+                    } else {
+                        protocol.h3
+                    };
+                    support.map(|value| value.to_string()).unwrap_or_default()
+                }
+            },
+            None => String::new(),
+        },
+        "title" | "length" | "sha256" | "mmh3" | "fingerprint" => match &result.body {
+            Some(body) => match name {
+                "title" => body.title.clone(),
+                "length" => body.length.to_string(),
+                "sha256" => body.sha256.clone(),
+                "mmh3" => body.mmh3.to_string(),
+                _ => body.matches.join(","),
+            },
+            None => String::new(),
+        },
+        _ => match name.strip_prefix("header:") {
+            Some(header) => result.header(header.trim()).unwrap_or_default(),
+            None => String::new(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result() -> ProbeResult {
+        ProbeResult {
+            scheme: "https".to_string(),
+            path: "/status".to_string(),
+            tags: vec!["hk".to_string(), "cf".to_string()],
+            response_time: 123.4,
+            colo: "HKG".to_string(),
+            country_code: "HK".to_string(),
+            server: "cloudflare".to_string(),
+            headers: vec![
+                ("Server".to_string(), "cloudflare".to_string()),
+                ("Set-Cookie".to_string(), "a=1".to_string()),
+                ("set-cookie".to_string(), "b=2".to_string()),
+            ],
+            ..ProbeResult::new_for_test("1.1.1.1", 2053)
+        }
+    }
+
+    fn render(template: &str) -> String {
+        template.parse::<LineTemplate>().unwrap().render(&result())
+    }
+
+    #[test]
+    fn renders_fields_and_literals() {
+        assert_eq!(
+            render("{ip}:{port}#{colo}-{latency}ms"),
+            "1.1.1.1:2053#HKG-123ms"
+        );
+        assert_eq!(
+            render("{target}{path} [{tags}]"),
+            "1.1.1.1:2053/status [hk,cf]"
+        );
+        assert_eq!(render("{url}{path}"), "https://1.1.1.1:2053/status");
+        assert_eq!(render("{ IP } {Status}"), "1.1.1.1 200");
+        assert_eq!(render("{{{colo}}}"), "{HKG}");
+        assert_eq!(render("plain"), "plain");
+    }
+
+    #[test]
+    fn renders_missing_values_as_empty() {
+        assert_eq!(
+            render("{speed}|{median}|{trace_loc}|{title}|{proxy}"),
+            "||||"
+        );
+        assert_eq!(render("{header:x-missing}"), "");
+    }
+
+    #[test]
+    fn renders_headers_case_insensitively() {
+        assert_eq!(render("{header:server}"), "cloudflare");
+        assert_eq!(render("{header: SET-COOKIE }"), "a=1, b=2");
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        assert!("{unknown}".parse::<LineTemplate>().is_err());
+        assert!("{ip".parse::<LineTemplate>().is_err());
+        assert!("{ip}}".parse::<LineTemplate>().is_err());
+        assert!("{header:}".parse::<LineTemplate>().is_err());
+    }
+
+    #[test]
+    fn detects_leading_url() {
+        let starts = |template: &str| template.parse::<LineTemplate>().unwrap().starts_with_url();
+        assert!(starts("{url}"));
+        assert!(starts("{url}#{colo}"));
+        assert!(starts("{url} {colo}"));
+        assert!(!starts("{colo} {url}"));
+        assert!(!starts("{target}"));
+        assert!(!starts("{url}{path}"));
+        assert!(!starts("{url}-{colo}"));
+    }
+}